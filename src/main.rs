@@ -1,18 +1,40 @@
+mod storage;
+
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, MouseEventKind, MouseButton},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyboardEnhancementFlags, MouseEventKind, MouseButton, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
+    },
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
 };
-use rand::seq::SliceRandom;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
 use ratatui::{
     backend::CrosstermBackend,
+    buffer::Buffer,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, BorderType, Clear, Paragraph, Wrap},
-    Frame, Terminal,
+    symbols,
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, BorderType, Clear, Paragraph, Wrap},
+    Frame, Terminal, TerminalOptions, Viewport,
 };
-use std::io;
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, IsTerminal, Write},
+    path::Path,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+    time::{Duration, Instant},
+};
+use storage::StorageError;
+use unicode_width::UnicodeWidthStr;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 enum Suit {
@@ -22,13 +44,246 @@ enum Suit {
     Diamonds,
 }
 
+const SUITS: [Suit; 4] = [Suit::Spades, Suit::Clubs, Suit::Hearts, Suit::Diamonds];
+
+/// Every non-ASCII mark the board draws outside its art, so a terminal that
+/// can't show them can swap the whole set at once.
+struct Glyphs {
+    suits: [&'static str; 4], // in SUITS order
+    cursor: &'static str,
+    separator: &'static str,
+    bar_full: &'static str,
+    bar_empty: &'static str,
+    stack: &'static str,
+    play_left: &'static str,
+    play_used: &'static str,
+    skipped_room: &'static str,
+    room: &'static str,
+    sparks: [&'static str; 8], // lowest to highest
+    bullet: &'static str,
+    emoji: bool, // emoji, block-letter art and box-drawn frames
+}
+
+const UNICODE_GLYPHS: Glyphs = Glyphs {
+    suits: ["♠", "♣", "♥", "♦"],
+    cursor: "▶",
+    separator: "│",
+    bar_full: "█",
+    bar_empty: "░",
+    stack: "▓",
+    play_left: "●",
+    play_used: "○",
+    skipped_room: "»",
+    room: "■",
+    sparks: ["▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"],
+    bullet: "•",
+    emoji: true,
+};
+
+const ASCII_GLYPHS: Glyphs = Glyphs {
+    suits: ["S", "C", "H", "D"],
+    cursor: ">",
+    separator: "|",
+    bar_full: "#",
+    bar_empty: "-",
+    stack: "#",
+    play_left: "o",
+    play_used: ".",
+    skipped_room: ">",
+    room: "#",
+    sparks: ["_", ".", "-", "~", "=", "+", "*", "#"],
+    bullet: "*",
+    emoji: false,
+};
+
+/// Set once at startup by `use_ascii_glyphs`.
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+impl Glyphs {
+    /// The set in use.
+    fn get() -> &'static Glyphs {
+        if ASCII.load(Ordering::Relaxed) { &ASCII_GLYPHS } else { &UNICODE_GLYPHS }
+    }
+
+    /// Block-letter art, or its plain stand-in under the ASCII set.
+    fn art(&self, blocks: &'static str, plain: &'static str) -> &'static str {
+        if self.emoji { blocks } else { plain }
+    }
+
+    /// Bar chart fills, from the same eight steps as the sparklines.
+    fn bar_set(&self) -> symbols::bar::Set {
+        let s = self.sparks;
+        symbols::bar::Set {
+            full: s[7],
+            seven_eighths: s[6],
+            three_quarters: s[5],
+            five_eighths: s[4],
+            half: s[3],
+            three_eighths: s[2],
+            one_quarter: s[1],
+            one_eighth: s[0],
+            empty: " ",
+        }
+    }
+
+    /// Each mark must be one column wide and unmistakable: no replacement
+    /// characters and no two suits alike, as happens when the source is read
+    /// under the wrong encoding.
+    fn validate(&self) -> Result<(), String> {
+        let marks = [
+            self.cursor,
+            self.separator,
+            self.bar_full,
+            self.bar_empty,
+            self.stack,
+            self.play_left,
+            self.play_used,
+            self.skipped_room,
+            self.room,
+            self.bullet,
+        ];
+        for mark in self.suits.iter().chain(&self.sparks).chain(&marks) {
+            if mark.contains('\u{FFFD}') || mark.width() != 1 {
+                return Err(format!("glyph {:?} isn't a single column", mark));
+            }
+        }
+        for (i, suit) in self.suits.iter().enumerate() {
+            if self.suits[..i].contains(suit) {
+                return Err(format!("suit glyph {:?} appears twice", suit));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether the locale says the terminal speaks UTF-8. With no locale set at
+/// all there's nothing to go on, so that counts as yes.
+fn locale_is_utf8() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .is_none_or(|value| {
+            let value = value.to_lowercase();
+            value.contains("utf-8") || value.contains("utf8")
+        })
+}
+
+/// Pick the glyph set: ASCII when asked for, when the locale isn't UTF-8, or
+/// when the Unicode set fails its check. Returns why it fell back, if it did
+/// on its own.
+fn use_ascii_glyphs(forced: bool) -> Option<String> {
+    let reason = if let Err(err) = UNICODE_GLYPHS.validate() {
+        Some(err)
+    } else if !locale_is_utf8() {
+        Some("the locale isn't UTF-8".to_string())
+    } else {
+        None
+    };
+    ASCII.store(forced || reason.is_some(), Ordering::Relaxed);
+    reason.filter(|_| !forced)
+}
+
+/// How much color the terminal can be trusted with.
+#[derive(Clone, Copy, PartialEq)]
+enum Palette {
+    Full,  // the 16 named colors
+    Basic, // only the 8 original ANSI colors: no bright or dark-gray shades
+    Mono,  // none at all
+}
+
+/// Set once at startup by `use_palette`.
+static PALETTE: AtomicU8 = AtomicU8::new(0);
+
+impl Palette {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "full" => Some(Palette::Full),
+            "basic" => Some(Palette::Basic),
+            "mono" => Some(Palette::Mono),
+            _ => None,
+        }
+    }
+
+    /// The palette in use.
+    fn get() -> Palette {
+        match PALETTE.load(Ordering::Relaxed) {
+            1 => Palette::Basic,
+            2 => Palette::Mono,
+            _ => Palette::Full,
+        }
+    }
+
+    /// A guess from the environment, and why it isn't `Full`: `NO_COLOR`
+    /// turns color off, as do a dumb or `-mono` `TERM`; the old hardware
+    /// terminals and the console get the basic eight.
+    fn detect() -> (Palette, Option<String>) {
+        if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return (Palette::Mono, None);
+        }
+        if std::env::var_os("COLORTERM").is_some_and(|v| !v.is_empty()) {
+            return (Palette::Full, None);
+        }
+        // An unset TERM is most likely a Windows console, which has color
+        let term = std::env::var("TERM").unwrap_or_default();
+        if term == "dumb" || term.ends_with("-mono") || term.ends_with("-m") {
+            (Palette::Mono, Some(format!("TERM={} has no color", term)))
+        } else if term.contains("256color") || term.contains("direct") {
+            (Palette::Full, None)
+        } else if term.starts_with("vt") || ["ansi", "linux", "cons25", "sun"].contains(&term.as_str()) {
+            (Palette::Basic, Some(format!("TERM={} has 8 colors", term)))
+        } else {
+            (Palette::Full, None)
+        }
+    }
+
+    /// `color` as this palette can show it. Bright shades fall back to
+    /// their base color; dark gray, which an 8-color terminal may draw as
+    /// black on black, becomes the default color (dimmed by `Theme::finish`).
+    fn map(&self, color: Color) -> Color {
+        match (self, color) {
+            (Palette::Full, _) | (_, Color::Reset) => color,
+            (Palette::Mono, _) => Color::Reset,
+            (Palette::Basic, Color::LightRed) => Color::Red,
+            (Palette::Basic, Color::LightGreen) => Color::Green,
+            (Palette::Basic, Color::LightYellow) => Color::Yellow,
+            (Palette::Basic, Color::LightBlue) => Color::Blue,
+            (Palette::Basic, Color::LightMagenta) => Color::Magenta,
+            (Palette::Basic, Color::LightCyan) => Color::Cyan,
+            (Palette::Basic, Color::Gray) => Color::White,
+            (
+                Palette::Basic,
+                Color::Black | Color::Red | Color::Green | Color::Yellow | Color::Blue | Color::Magenta
+                | Color::Cyan | Color::White,
+            ) => color,
+            (Palette::Basic, _) => Color::Reset,
+        }
+    }
+}
+
+/// Pick the palette: the one asked for, else a guess from the environment.
+/// Returns why the guess held back on color, if it did.
+fn use_palette(chosen: Option<Palette>) -> Option<String> {
+    let (palette, reason) = match chosen {
+        Some(palette) => (palette, None),
+        None => Palette::detect(),
+    };
+    PALETTE.store(palette as u8, Ordering::Relaxed);
+    reason
+}
+
 impl Suit {
-    fn symbol(&self) -> &str {
+    fn symbol(&self) -> &'static str {
+        let index = SUITS.iter().position(|s| s == self).unwrap_or(0);
+        Glyphs::get().suits[index]
+    }
+
+    fn name(&self) -> &'static str {
         match self {
-            Suit::Spades => "♠",
-            Suit::Clubs => "♣",
-            Suit::Hearts => "♥",
-            Suit::Diamonds => "♦",
+            Suit::Spades => "Spades",
+            Suit::Clubs => "Clubs",
+            Suit::Hearts => "Hearts",
+            Suit::Diamonds => "Diamonds",
         }
     }
 
@@ -40,14 +295,21 @@ impl Suit {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 struct Card {
     suit: Suit,
     rank: u8, // 2-14 (11=J, 12=Q, 13=K, 14=A)
 }
 
+/// Set once at startup from `--numeric-ranks`.
+static NUMERIC_RANKS: AtomicBool = AtomicBool::new(false);
+
 impl Card {
+    /// "Q", or "12" under `--numeric-ranks`.
     fn rank_str(&self) -> String {
+        if NUMERIC_RANKS.load(Ordering::Relaxed) {
+            return self.rank.to_string();
+        }
         match self.rank {
             11 => "J".to_string(),
             12 => "Q".to_string(),
@@ -61,6 +323,86 @@ impl Card {
         format!("{}{}", self.rank_str(), self.suit.symbol())
     }
 
+    /// A full sentence without symbols, for screen readers: "Card 1: Spades
+    /// 10, monster, 10 damage (weapon reduces to 3)."
+    fn describe(&self, slot: usize, game: &GameState) -> String {
+        let rank = match self.rank {
+            11 => "Jack".to_string(),
+            12 => "Queen".to_string(),
+            13 => "King".to_string(),
+            14 => "Ace".to_string(),
+            n => n.to_string(),
+        };
+        let effect = if self.is_monster() {
+            match game.weapon {
+                Some(ref w) if game.can_use_weapon_on(self) => format!(
+                    "monster, {} damage (weapon reduces to {})",
+                    self.value(),
+                    game.settings.combat.damage(self.value(), w.card.value())
+                ),
+                Some(_) => format!("monster, {} damage (too strong for your weapon)", self.value()),
+                None => format!("monster, {} damage", self.value()),
+            }
+        } else if self.is_weapon() {
+            match game.weapon {
+                Some(ref w) => format!(
+                    "weapon, {} attack power (replaces your {})",
+                    self.value(),
+                    w.card.value()
+                ),
+                None => format!("weapon, {} attack power", self.value()),
+            }
+        } else if let Some(heal) = game.potion_heal(self) {
+            let when = if game.settings.potions.heals_at_turn_end() { " at the end of the turn" } else { "" };
+            format!("potion, heals {} HP{}", heal, when)
+        } else if game.settings.potions == PotionRule::Best {
+            "potion, wasted: you already drank one at least as big this turn".to_string()
+        } else {
+            "potion, wasted: you already drank one this turn".to_string()
+        };
+        format!("Card {}: {} {}, {}.", slot + 1, self.suit.name(), rank, effect)
+    }
+
+    /// What lurks behind a monster card: spades are the restless dead,
+    /// clubs the beasts and brutes. `None` for potions and weapons.
+    fn monster_name(&self) -> Option<&'static str> {
+        const SPADES: [&str; 13] = [
+            "Crypt Rat", "Bat Swarm", "Zombie", "Ghoul", "Skeleton Warrior", "Wraith", "Banshee",
+            "Vampire Spawn", "Revenant", "Grave Knight", "Bone Queen", "Death Knight", "Lich",
+        ];
+        const CLUBS: [&str; 13] = [
+            "Kobold", "Giant Spider", "Goblin Scout", "Dire Wolf", "Orc Grunt", "Goblin Pack", "Bugbear",
+            "Ogre", "Troll", "Minotaur", "Hag Matriarch", "Giant Chieftain", "Dragon",
+        ];
+        let names = match self.suit {
+            Suit::Spades => &SPADES,
+            Suit::Clubs => &CLUBS,
+            _ => return None,
+        };
+        names.get(self.rank.checked_sub(2)? as usize).copied()
+    }
+
+    /// Parse a suit letter and rank as typed on the command line, e.g. `D8`
+    /// or `SQ`.
+    fn parse(code: &str) -> Option<Card> {
+        let mut chars = code.chars();
+        let suit = match chars.next()?.to_ascii_uppercase() {
+            'S' => Suit::Spades,
+            'C' => Suit::Clubs,
+            'H' => Suit::Hearts,
+            'D' => Suit::Diamonds,
+            _ => return None,
+        };
+        let rank = match chars.as_str().to_ascii_uppercase().as_str() {
+            "J" => 11,
+            "Q" => 12,
+            "K" => 13,
+            "A" => 14,
+            n => n.parse().ok().filter(|r| (2..=10).contains(r))?,
+        };
+        Some(Card { suit, rank })
+    }
+
     fn is_monster(&self) -> bool {
         matches!(self.suit, Suit::Spades | Suit::Clubs)
     }
@@ -87,6 +429,19 @@ impl Card {
         }
     }
 
+    /// Position in a full 52-card deck, suits in SUITS order then ranks 2-14.
+    fn index(&self) -> usize {
+        let suit = SUITS.iter().position(|&s| s == self.suit).unwrap();
+        suit * 13 + (self.rank as usize - 2)
+    }
+
+    fn from_index(index: usize) -> Option<Card> {
+        let suit = *SUITS.get(index / 13)?;
+        let card = Card { suit, rank: (index % 13) as u8 + 2 };
+        // Red suits stop at 10
+        (card.is_monster() || card.rank <= 10).then_some(card)
+    }
+
     fn type_label(&self) -> &str {
         if self.is_monster() {
             "MONSTER"
@@ -105,10 +460,19 @@ struct Weapon {
 }
 
 impl Weapon {
-    fn can_use_against(&self, monster_value: u8) -> bool {
-        match self.last_monster_slain {
+    fn can_use_against(&self, monster_value: u8, durability: Durability) -> bool {
+        match self.max_hittable(durability) {
             None => true,
-            Some(last) => monster_value < last,  // Strictly less than, weapon degrades
+            Some(max) => monster_value <= max,
+        }
+    }
+
+    /// Highest monster value this weapon can still hit, or None if unlimited.
+    fn max_hittable(&self, durability: Durability) -> Option<u8> {
+        match (self.last_monster_slain, durability) {
+            (None, _) | (_, Durability::Off) => None,
+            (Some(last), Durability::Strict) => Some(last - 1), // Strictly less than, weapon degrades
+            (Some(last), Durability::Equal) => Some(last),
         }
     }
 }
@@ -118,560 +482,4839 @@ enum Screen {
     Game,
     Combat,
     Help,
+    CardHelp, // the rules for the selected card's type
+    DullingHelp, // the dulling rule, worked through for the weapon held
     Log,
     GameOver,
     ConfirmQuit,
+    Export,
+    Stats,
+    Graveyard,
+    Results,
+    TurnSummary,
+    Resume,
+    Histogram,
+    Paused,
+    ConfirmSkip,
+    ConfirmMulligan,
+    DebugPrompt, // --debug only
+    DebugDeck,
 }
 
-struct GameState {
-    dungeon: Vec<Card>,
-    room: Vec<Card>,
-    discard: Vec<Card>,
-    health: i32,
-    max_health: i32,
-    weapon: Option<Weapon>,
-    monsters_on_weapon: Vec<Card>,
-    cards_played_this_turn: u8,
-    potion_used_this_turn: bool,
-    just_skipped: bool,
-    game_over: bool,
-    won: bool,
-    last_card_was_potion: Option<Card>,
-    log: Vec<String>,
-    turn_number: u32,
-    selected_index: usize,
-    screen: Screen,
-    combat_card_index: Option<usize>,
-    combat_selection: usize, // 0 = weapon, 1 = barehanded, 2 = back
-    message: String,
-    card_areas: Vec<Rect>, // Store card positions for mouse clicks
-    combat_button_areas: Vec<Rect>, // Store combat button positions
+const USAGE: &str = "Usage: scoundrel [OPTIONS]
+
+Options:
+  --difficulty <NAME>       easy, normal, or hard (other rule flags override it)
+  --max-hp <N>              Starting and maximum health
+  --durability <RULE>       strict, equal (can hit equal values), or off
+  --potions <RULE>          strict (one per turn), forgiving (all heal) or best (largest heals)
+  --red-cards <N>           Highest potion/weapon rank in the deck (2-10)
+  --classic-bonus <on|off>  Score the last potion when winning at full HP
+  --skip-penalty <KIND>     off, hp (skips cost 1 HP) or score (1 point)
+  --skip-order <WHERE>      Skipped cards go to the bottom, shuffle, or front
+  --combat <FORMULA>        classic, or chip (weapon fights always deal 1+)
+  --no-carry                Play all 4 cards of every room; none carry over
+  --two-weapons             Keep a spare weapon and pick which one each fight
+  --mulligan                Offer one redeal when the first room can't be survived
+  --ironman                 One run, no undo or redeal; the game over records and exits
+  --simulate                Play headless with the built-in greedy strategy
+  --games <N>               Number of games for --simulate (default 1)
+  --solve <SEED>            Search the seed's dungeon for a win and print the best line
+  --seed <N>                Deal the dungeon from a fixed seed
+  --puzzle <CODE>           Play the exact dungeon from an exported puzzle code
+  --ghost                   Compare HP against your last run on the same seed
+  --threat-colors           Tint monsters by threat relative to your weapon
+  --equip-hints             Point out weapons in the room while you have none
+  --effects                 Float damage numbers over fought monsters
+  --auto-advance            Let A play the only safe move (after a confirm)
+  --minimap                 Show a strip of past rooms above the controls
+  --turn-summary            Pause on a recap of each finished room
+  --autosave                Checkpoint every room and offer to resume on launch
+  --leaderboard <FILE>      Add each run to a shared scoreboard under a name
+  --assist                  Weigh each room's cheapest line against skipping;
+                            also show the highest score still possible
+  --emit-events <PATH>      Write one JSON line per game event (- for stdout)
+  --log-json <PATH>         Write the adventure log as JSON lines (- for stdout)
+  --log-limit <N>           Log entries kept in memory (default 2000)
+  --message-timeout <SECS>  Clear the message line after SECS idle (0 = never)
+  --start-weapon <CARD>     Begin holding a weapon, e.g. D8 (diamonds 2-10)
+  --no-animations           Keep the screen still (no pulsing or flashing)
+  --deal-animation          Deal each room's new cards in one at a time
+  --no-confirm-quit         Let q quit without asking (Q never asks)
+  --no-confirm-endgame-skip Skip the last rooms without being asked first
+  --confirm-fights          Ask before every fight, even unarmed
+  --safe                    Confirm fatal fights and quits, and let U undo
+  --expanded-controls       Show every key, grouped on four lines (K toggles)
+  --no-wrap-navigation      Stop Left/Right at the ends of the room
+  --hold-repeat             Keep moving while Left/Right is held, speeding up
+  --no-altscreen            Draw inline below the prompt, not on a separate screen
+  --minimal                 Plain borders, no emoji or color (for recordings)
+  --no-flavor               Leave monster names out of the info line and combat
+  --lethal-warning          At low HP, box the info line when a monster kills barehanded
+  --ascii                   Draw suits and marks in plain ASCII (S, C, H, D)
+  --numeric-ranks           Show J, Q, K and A as 11, 12, 13 and 14
+  --colors <MODE>           auto (default; honors NO_COLOR and TERM), full, basic or mono
+  --banner <NAME>           Title: classic, suits, blades or torches
+  --card-border <NAME>      Card frames: rounded, plain or blocky
+  --keys <MAP>              standard, or vim (h/l/j/k move; Shift+L log, Shift+K keys)
+  -h, --help                Print this help
+
+Any option can also be set as SCOUNDREL_<NAME> (e.g. SCOUNDREL_MAX_HP=15)
+or as `name = value` in ~/.config/scoundrel/config. Flags beat the
+environment, which beats the config file.";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
 }
 
-impl GameState {
-    fn new() -> Self {
-        let mut state = GameState {
-            dungeon: Vec::new(),
-            room: Vec::new(),
-            discard: Vec::new(),
-            health: 20,
-            max_health: 20,
-            weapon: None,
-            monsters_on_weapon: Vec::new(),
-            cards_played_this_turn: 0,
-            potion_used_this_turn: false,
-            just_skipped: false,
-            game_over: false,
-            won: false,
-            last_card_was_potion: None,
-            log: Vec::new(),
-            turn_number: 1,
-            selected_index: 0,
-            screen: Screen::Game,
-            combat_card_index: None,
-            combat_selection: 0,
-            message: String::new(),
-            card_areas: Vec::new(),
-            combat_button_areas: Vec::new(),
-        };
-        state.setup_deck();
-        state.log("Entered the dungeon with 20 HP".to_string());
-        state.deal_room();
-        state
+impl Difficulty {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "easy" => Some(Difficulty::Easy),
+            "normal" => Some(Difficulty::Normal),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
     }
+}
+
+/// How a weapon dulls after each kill.
+#[derive(Clone, Copy, PartialEq)]
+enum Durability {
+    Strict, // only monsters weaker than the last kill
+    Equal,  // monsters up to and including the last kill
+    Off,    // never dulls
+}
 
-    fn log(&mut self, msg: String) {
-        self.log.push(format!("[Turn {}] {}", self.turn_number, msg));
+impl Durability {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "strict" => Some(Durability::Strict),
+            "equal" => Some(Durability::Equal),
+            "off" => Some(Durability::Off),
+            _ => None,
+        }
     }
 
-    fn setup_deck(&mut self) {
-        self.dungeon.clear();
-        // Black suits: full range 2-14
-        for suit in [Suit::Spades, Suit::Clubs] {
-            for rank in 2..=14 {
-                self.dungeon.push(Card { suit, rank });
+    /// The rule in a sentence, for the help popups.
+    fn rule(&self) -> &'static str {
+        match self {
+            Durability::Strict => {
+                "Once it has slain a monster, a weapon can only hit monsters of LOWER value than its last kill."
             }
-        }
-        // Red suits: only 2-10 (no face cards or aces)
-        for suit in [Suit::Hearts, Suit::Diamonds] {
-            for rank in 2..=10 {
-                self.dungeon.push(Card { suit, rank });
+            Durability::Equal => {
+                "Once it has slain a monster, a weapon can only hit monsters of equal or lower value \
+                 than its last kill."
             }
+            Durability::Off => "Weapons never dull: they can hit any monster.",
         }
-        let mut rng = rand::thread_rng();
-        self.dungeon.shuffle(&mut rng);
     }
 
-    fn deal_room(&mut self) {
-        while self.room.len() < 4 && !self.dungeon.is_empty() {
-            self.room.push(self.dungeon.remove(0));
+    fn name(&self) -> &'static str {
+        match self {
+            Durability::Strict => "strict dulling",
+            Durability::Equal => "equal-value dulling",
+            Durability::Off => "no dulling",
         }
-        self.cards_played_this_turn = 0;
-        self.potion_used_this_turn = false;
-        self.last_card_was_potion = None;
-        self.selected_index = 0;
+    }
+}
 
-        if !self.room.is_empty() {
-            let room_str: Vec<String> = self.room.iter().map(|c| c.display()).collect();
-            self.log(format!("Entered room: {}", room_str.join(", ")));
+#[derive(Clone, Copy, PartialEq)]
+enum PotionRule {
+    OnePerTurn, // the second potion in a turn is wasted
+    Forgiving,  // every potion heals
+    Best,       // the largest potion in a turn is the one that heals
+}
+
+impl PotionRule {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "strict" => Some(PotionRule::OnePerTurn),
+            "forgiving" => Some(PotionRule::Forgiving),
+            "best" => Some(PotionRule::Best),
+            _ => None,
         }
     }
 
-    fn play_potion(&mut self, index: usize) {
-        let card = self.room.remove(index);
+    fn name(&self) -> &'static str {
+        match self {
+            PotionRule::OnePerTurn => "one potion per turn",
+            PotionRule::Forgiving => "every potion heals",
+            PotionRule::Best => "largest potion per turn heals",
+        }
+    }
 
-        if self.potion_used_this_turn {
-            self.message = format!("Second potion - {} wasted!", card.display());
-            self.log(format!("Wasted {} (already used potion)", card.display()));
-        } else {
-            let heal = (card.value() as i32).min(self.max_health - self.health);
-            self.health += heal;
-            self.potion_used_this_turn = true;
-            self.last_card_was_potion = Some(card);
-            self.message = format!("Used {} - healed {} HP!", card.display(), heal);
-            self.log(format!(
-                "Drank {}, healed {} HP (now {} HP)",
-                card.display(),
-                heal,
-                self.health
-            ));
+    /// Whether `potion` counts, given the value of the potion already
+    /// counted this turn; one that doesn't is wasted. Under `Best` a bigger
+    /// potion takes over from the counted one.
+    fn counts(&self, potion: u8, counted: Option<u8>) -> bool {
+        match (self, counted) {
+            (_, None) | (PotionRule::Forgiving, _) => true,
+            (PotionRule::Best, Some(value)) => potion > value,
+            (PotionRule::OnePerTurn, Some(_)) => false,
         }
+    }
 
-        self.discard.push(card);
-        self.cards_played_this_turn += 1;
-        self.check_turn_complete();
+    /// `Best` can't know the turn's largest potion until the turn is over,
+    /// so its potion heals then instead of when drunk.
+    fn heals_at_turn_end(&self) -> bool {
+        *self == PotionRule::Best
     }
+}
 
-    fn play_weapon(&mut self, index: usize) {
-        let card = self.room.remove(index);
+/// Where a skipped room's cards go back into the dungeon.
+#[derive(Clone, Copy, PartialEq)]
+enum SkipOrder {
+    Bottom,  // under everything: dodged until the very end, which gets harder
+    Shuffle, // anywhere at random: they may come straight back or never
+    Front,   // under the next room: a skip only buys one room's delay
+}
 
-        if let Some(ref old_weapon) = self.weapon {
-            let old = old_weapon.card.display();
-            self.discard.push(old_weapon.card);
-            self.discard.extend(self.monsters_on_weapon.drain(..));
-            self.log(format!("Discarded {}, equipped {}", old, card.display()));
-        } else {
-            self.log(format!("Equipped {}", card.display()));
+impl SkipOrder {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bottom" => Some(SkipOrder::Bottom),
+            "shuffle" => Some(SkipOrder::Shuffle),
+            "front" => Some(SkipOrder::Front),
+            _ => None,
         }
-
-        self.weapon = Some(Weapon {
-            card,
-            last_monster_slain: None,
-        });
-        self.last_card_was_potion = None;
-        self.message = format!("Equipped {}!", card.display());
-
-        self.cards_played_this_turn += 1;
-        self.check_turn_complete();
     }
 
-    fn can_use_weapon_on(&self, card: &Card) -> bool {
-        if let Some(ref weapon) = self.weapon {
-            weapon.can_use_against(card.value())
-        } else {
-            false
+    fn name(&self) -> &'static str {
+        match self {
+            SkipOrder::Bottom => "skips to the bottom",
+            SkipOrder::Shuffle => "skips shuffled in",
+            SkipOrder::Front => "skips come back next",
         }
     }
+}
 
-    fn fight_monster(&mut self, index: usize, use_weapon: bool) {
-        let card = self.room.remove(index);
+/// What it costs to skip a room, if anything.
+#[derive(Clone, Copy, PartialEq)]
+enum SkipPenalty {
+    Off,
+    Health, // lose SKIP_PENALTY HP on the spot
+    Score,  // lose SKIP_PENALTY points at the end
+}
 
-        let damage = if use_weapon {
-            let weapon = self.weapon.as_mut().unwrap();
-            let dmg = (card.value() as i32 - weapon.card.value() as i32).max(0);
-            weapon.last_monster_slain = Some(card.value());
-            let weapon_display = weapon.card.display();
-            let card_display = card.display();
-            self.monsters_on_weapon.push(card);
-            self.message = format!("Slew {} with weapon - took {} damage!", card_display, dmg);
-            self.log(format!(
-                "Killed {} with {}, took {} dmg (now {} HP)",
-                card_display,
-                weapon_display,
-                dmg,
-                self.health - dmg
-            ));
-            dmg
-        } else {
-            let dmg = card.value() as i32;
-            self.discard.push(card);
-            self.message = format!("Fought {} barehanded - took {} damage!", card.display(), dmg);
-            self.log(format!(
-                "Fought {} barehanded, took {} dmg (now {} HP)",
-                card.display(),
-                dmg,
-                self.health - dmg
-            ));
-            dmg
-        };
+const SKIP_PENALTY: i32 = 1;
 
-        self.health -= damage;
-        self.last_card_was_potion = None;
-        self.cards_played_this_turn += 1;
+impl SkipPenalty {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(SkipPenalty::Off),
+            "hp" => Some(SkipPenalty::Health),
+            "score" => Some(SkipPenalty::Score),
+            _ => None,
+        }
+    }
 
-        if self.health <= 0 {
-            self.health = 0;
-            self.game_over = true;
-            self.won = false;
-            self.log("DIED!".to_string());
-            self.screen = Screen::GameOver;
-        } else {
-            self.check_turn_complete();
+    fn name(&self) -> &'static str {
+        match self {
+            SkipPenalty::Off => "free skips",
+            SkipPenalty::Health => "skips cost 1 HP",
+            SkipPenalty::Score => "skips cost 1 point",
         }
     }
+}
 
-    fn check_turn_complete(&mut self) {
-        if self.cards_played_this_turn >= 3 {
-            self.turn_number += 1;
+/// The look of the board. Every style choice that `--minimal` changes goes
+/// through here.
+#[derive(Clone, Copy, PartialEq)]
+enum Theme {
+    Standard,
+    Minimal, // no emoji, plain borders, no color, for recordings and docs
+}
 
-            if self.dungeon.is_empty() && self.room.len() == 1 {
-                // Must play final card
-                self.message = "Final card! You must face it.".to_string();
-                self.cards_played_this_turn = 0;
-                self.potion_used_this_turn = false;
-                self.selected_index = 0;
-            } else if self.dungeon.is_empty() && self.room.is_empty() {
-                self.game_over = true;
-                self.won = true;
-                self.log(format!("VICTORY! Score: {}", self.calculate_score()));
-                self.screen = Screen::GameOver;
-            } else {
-                self.just_skipped = false;
-                self.deal_room();
-            }
+impl Theme {
+    /// The theme after this one, for C's live preview.
+    fn next(&self) -> Self {
+        match self {
+            Theme::Standard => Theme::Minimal,
+            Theme::Minimal => Theme::Standard,
         }
+    }
 
-        if self.selected_index >= self.room.len() && !self.room.is_empty() {
-            self.selected_index = self.room.len() - 1;
+    fn name(&self) -> &'static str {
+        match self {
+            Theme::Standard => "standard",
+            Theme::Minimal => "minimal (as --minimal)",
         }
     }
 
-    fn skip_room(&mut self) {
-        if self.just_skipped {
-            self.message = "Cannot skip two rooms in a row!".to_string();
-            return;
-        }
-        if self.cards_played_this_turn > 0 {
-            self.message = "Cannot skip after playing cards!".to_string();
-            return;
+    /// A decorative border in the given style, or a plain one.
+    fn border(&self, styled: BorderType) -> BorderType {
+        match self {
+            Theme::Standard => styled,
+            Theme::Minimal => BorderType::Plain,
         }
+    }
 
-        let room_str: Vec<String> = self.room.iter().map(|c| c.display()).collect();
-        self.dungeon.extend(self.room.drain(..));
-        self.just_skipped = true;
-        self.log(format!("Skipped room ({})", room_str.join(", ")));
-        self.message = "Skipped room".to_string();
-        self.deal_room();
+    /// An emoji (with its spacing), or nothing.
+    fn icon(&self, icon: &'static str) -> &'static str {
+        match self {
+            Theme::Standard if Glyphs::get().emoji => icon,
+            _ => "",
+        }
     }
 
-    fn calculate_score(&self) -> i32 {
-        if self.won {
-            let mut score = self.health;
-            if self.health == self.max_health {
-                if let Some(ref potion) = self.last_card_was_potion {
-                    score += potion.value() as i32;
+    /// Fit a drawn frame to the terminal's palette, or wash every color
+    /// out of it for the minimal theme, keeping bold and the like so the
+    /// selection still shows. Under the ASCII set, box-drawn borders and
+    /// the odd dot or dash are redrawn in ASCII too.
+    fn finish(&self, buffer: &mut Buffer) {
+        if !Glyphs::get().emoji {
+            for cell in buffer.content.iter_mut() {
+                if let Some(plain) = ascii_stand_in(cell.symbol()) {
+                    cell.set_symbol(plain);
                 }
             }
-            score
-        } else {
-            let remaining: i32 = self
-                .dungeon
-                .iter()
-                .chain(self.room.iter())
-                .filter(|c| c.is_monster())
-                .map(|c| c.value() as i32)
-                .sum();
-            self.health - remaining
+        }
+        let palette = if *self == Theme::Minimal { Palette::Mono } else { Palette::get() };
+        if palette == Palette::Full {
+            return;
+        }
+        for cell in buffer.content.iter_mut() {
+            if palette == Palette::Basic && cell.fg == Color::DarkGray {
+                cell.modifier.insert(Modifier::DIM);
+            }
+            let (fg, bg) = (palette.map(cell.fg), palette.map(cell.bg));
+            cell.set_fg(fg).set_bg(bg);
+        }
+    }
+}
+
+/// The ASCII stand-in for a cell of any border ratatui draws, or of the
+/// punctuation the text around the board is written with.
+fn ascii_stand_in(symbol: &str) -> Option<&'static str> {
+    match symbol {
+        "·" | "–" | "—" => Some("-"),
+        "…" => Some("."),
+        "≈" => Some("~"),
+        "↑" => Some("^"),
+        "↓" => Some("v"),
+        "─" | "━" | "═" | "▀" | "▄" => Some("-"),
+        "│" | "┃" | "║" | "▌" | "▐" => Some("|"),
+        "┌" | "┐" | "└" | "┘" | "╭" | "╮" | "╰" | "╯" | "╔" | "╗" | "╚" | "╝" | "┏" | "┓" | "┗" | "┛" | "▛" | "▜"
+        | "▙" | "▟" => Some("+"),
+        _ => None,
+    }
+}
+
+/// The words in the title bar.
+#[derive(Clone, Copy, PartialEq)]
+enum Banner {
+    Classic, // ~ SCOUNDREL ~
+    Suits,   // the four suits either side
+    Blades,  // plain ASCII flourishes
+    Torches, // an emoji each side, where emoji are drawn
+}
+
+impl Banner {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "classic" => Some(Banner::Classic),
+            "suits" => Some(Banner::Suits),
+            "blades" => Some(Banner::Blades),
+            "torches" => Some(Banner::Torches),
+            _ => None,
+        }
+    }
+
+    fn text(&self, theme: Theme) -> String {
+        match self {
+            Banner::Classic => "~ SCOUNDREL ~".to_string(),
+            Banner::Suits => {
+                let suits = Glyphs::get().suits;
+                format!("{} {}  SCOUNDREL  {} {}", suits[0], suits[2], suits[3], suits[1])
+            }
+            Banner::Blades => "-=[ S C O U N D R E L ]=-".to_string(),
+            Banner::Torches => {
+                let torch = theme.icon("🔥 ");
+                format!("{}SCOUNDREL {}", torch, torch).trim_end().to_string()
+            }
+        }
+    }
+}
+
+/// The frame drawn around each room card when it isn't selected or hinted.
+#[derive(Clone, Copy, PartialEq)]
+enum CardBorder {
+    Rounded,
+    Plain,
+    Blocky, // half-block quadrants, like a printed card edge
+}
+
+impl CardBorder {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rounded" => Some(CardBorder::Rounded),
+            "plain" => Some(CardBorder::Plain),
+            "blocky" => Some(CardBorder::Blocky),
+            _ => None,
+        }
+    }
+
+    fn border_type(&self) -> BorderType {
+        match self {
+            CardBorder::Rounded => BorderType::Rounded,
+            CardBorder::Plain => BorderType::Plain,
+            CardBorder::Blocky => BorderType::QuadrantOutside,
+        }
+    }
+}
+
+/// A ready-made set of keys, laid over the standard ones.
+#[derive(Clone, Copy, PartialEq)]
+enum KeyMap {
+    Standard,
+    Vim, // h/l and j/k move; the log and key list move to Shift+L and Shift+K
+}
+
+impl KeyMap {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "standard" => Some(KeyMap::Standard),
+            "vim" => Some(KeyMap::Vim),
+            _ => None,
+        }
+    }
+
+    /// The standard key `code` stands for on `screen`; everything after
+    /// this only knows the standard keys.
+    fn translate(&self, screen: Screen, code: KeyCode) -> KeyCode {
+        match (self, screen, code) {
+            (KeyMap::Standard, _, _) => code,
+            (KeyMap::Vim, Screen::Game, KeyCode::Char('h')) => KeyCode::Left,
+            (KeyMap::Vim, Screen::Game, KeyCode::Char('l')) => KeyCode::Right,
+            (KeyMap::Vim, Screen::Game | Screen::GameOver, KeyCode::Char('L')) => KeyCode::Char('l'),
+            (KeyMap::Vim, Screen::Game, KeyCode::Char('K')) => KeyCode::Char('k'),
+            (KeyMap::Vim, Screen::Game | Screen::Combat | Screen::Results, KeyCode::Char('j')) => KeyCode::Down,
+            (KeyMap::Vim, Screen::Game | Screen::Combat | Screen::Results, KeyCode::Char('k')) => KeyCode::Up,
+            _ => code,
+        }
+    }
+
+    /// A controls-line label as it reads under this key map.
+    fn label(&self, label: &'static str) -> &'static str {
+        match (self, label) {
+            (KeyMap::Vim, "Tab/Arrows: move") => "h/l/j/k: move",
+            (KeyMap::Vim, "L: log") => "Shift+L: log",
+            (KeyMap::Vim, "K: more keys") => "Shift+K: more keys",
+            (KeyMap::Vim, "K: fewer keys") => "Shift+K: fewer keys",
+            _ => label,
+        }
+    }
+}
+
+/// How a weapon reduces a monster's damage.
+#[derive(Clone, Copy, PartialEq)]
+enum CombatFormula {
+    Classic, // monster minus weapon, down to 0
+    Chip,    // monster minus weapon, but always at least 1
+}
+
+impl CombatFormula {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "classic" => Some(CombatFormula::Classic),
+            "chip" => Some(CombatFormula::Chip),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            CombatFormula::Classic => "classic combat",
+            CombatFormula::Chip => "chip damage",
+        }
+    }
+
+    /// Damage taken fighting `monster` with a weapon of value `weapon`.
+    fn damage(&self, monster: u8, weapon: u8) -> i32 {
+        let reduced = monster as i32 - weapon as i32;
+        match self {
+            CombatFormula::Classic => reduced.max(0),
+            CombatFormula::Chip => reduced.max(1),
+        }
+    }
+
+    /// The sum behind `damage`, short enough for a card face.
+    fn preview(&self, monster: u8, weapon: u8) -> String {
+        let damage = self.damage(monster, weapon);
+        if *self == CombatFormula::Chip && damage > monster as i32 - weapon as i32 {
+            format!("{} chip dmg", damage)
+        } else {
+            format!("{}-{}={} dmg", monster, weapon, damage)
+        }
+    }
+}
+
+/// Options chosen on the command line, fixed for the whole session.
+#[derive(Clone)]
+struct Settings {
+    difficulty: Difficulty,
+    max_health: i32,
+    durability: Durability,
+    potions: PotionRule,
+    red_max_rank: u8, // potions and weapons run 2..=red_max_rank
+    classic_bonus: bool,
+    skip_penalty: SkipPenalty,
+    skip_order: SkipOrder,
+    combat: CombatFormula,
+    carry: bool, // the unplayed 4th card stays for the next room
+    simulate: bool,
+    solve: Option<u64>, // seed to search instead of playing
+    games: u32,
+    seed: Option<u64>,
+    puzzle: Option<Vec<Card>>,
+    ghost: bool,
+    threat_colors: bool,
+    equip_hints: bool,
+    effects: bool,
+    animations: bool,
+    deal_animation: bool, // new cards come in one at a time (needs animations)
+    auto_advance: bool,
+    minimap: bool,
+    emit_events: Option<String>, // path, or - for stdout
+    log_json: Option<String>,    // likewise
+    leaderboard: Option<String>, // shared file runs are ranked in
+    log_limit: usize, // entries kept in memory; older ones are dropped
+    message_timeout: u32, // seconds without input before the message clears; 0 keeps it
+    start_weapon: Option<Card>,
+    turn_summary: bool,
+    assist: bool,
+    confirm_quit: bool, // q asks first; Q never does
+    confirm_endgame_skip: bool, // S asks first once the dungeon is nearly through
+    autosave: bool,
+    theme: Theme,
+    banner: Banner,
+    card_border: CardBorder,
+    keys: KeyMap,
+    colors: Option<Palette>, // None: detect
+    ascii: bool,
+    numeric_ranks: bool, // 11-14 in place of J/Q/K/A
+    wrap_navigation: bool, // Left/Right run off one end onto the other
+    hold_repeat: bool, // a held Left/Right keeps stepping; needs key release events
+    altscreen: bool, // off: draw inline, leaving the last frame in the scrollback
+    confirm_fights: bool, // every monster opens the combat choice, armed or not
+    two_weapons: bool, // a spare weapon slot, chosen per fight
+    mulligan: bool, // M redeals a hopeless first room, once
+    ironman: bool, // one run per launch: no undo, mulligan or restart, and the game over exits
+    flavor: bool, // monster names next to monster cards
+    lethal_warning: bool, // at low HP, a boxed warning for monsters that kill barehanded
+    safe: bool, // confirm anything that ends the run, and allow undo
+    expanded_controls: bool, // four grouped lines of keys instead of one; K toggles
+    debug: bool, // hidden: ` opens a prompt for hp/card/deck/win/lose; nothing is saved
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings::preset(Difficulty::Normal)
+    }
+}
+
+impl Settings {
+    /// The bundle of rules behind each `--difficulty`.
+    fn preset(difficulty: Difficulty) -> Self {
+        let (max_health, durability, potions, red_max_rank) = match difficulty {
+            Difficulty::Easy => (25, Durability::Off, PotionRule::Forgiving, 10),
+            Difficulty::Normal => (20, Durability::Strict, PotionRule::OnePerTurn, 10),
+            Difficulty::Hard => (15, Durability::Strict, PotionRule::OnePerTurn, 8),
+        };
+        Settings {
+            difficulty,
+            max_health,
+            durability,
+            potions,
+            red_max_rank,
+            classic_bonus: true,
+            skip_penalty: SkipPenalty::Off,
+            skip_order: SkipOrder::Bottom,
+            combat: CombatFormula::Classic,
+            carry: true,
+            simulate: false,
+            solve: None,
+            games: 1,
+            seed: None,
+            puzzle: None,
+            ghost: false,
+            threat_colors: false,
+            equip_hints: false,
+            effects: false,
+            animations: true,
+            deal_animation: false,
+            auto_advance: false,
+            minimap: false,
+            emit_events: None,
+            log_json: None,
+            leaderboard: None,
+            log_limit: 2000,
+            message_timeout: 0,
+            start_weapon: None,
+            turn_summary: false,
+            assist: false,
+            confirm_quit: true,
+            confirm_endgame_skip: true,
+            autosave: false,
+            theme: Theme::Standard,
+            banner: Banner::Classic,
+            card_border: CardBorder::Rounded,
+            keys: KeyMap::Standard,
+            colors: None,
+            ascii: false,
+            numeric_ranks: false,
+            wrap_navigation: true,
+            hold_repeat: false,
+            altscreen: true,
+            confirm_fights: false,
+            two_weapons: false,
+            mulligan: false,
+            ironman: false,
+            flavor: true,
+            lethal_warning: false,
+            safe: false,
+            expanded_controls: false,
+            debug: false,
+        }
+    }
+
+    /// Layer the sources over the defaults, lowest precedence first: the
+    /// config file, then `SCOUNDREL_*` environment variables, then the
+    /// command line, with the difficulty preset under all three. Also
+    /// returns warnings about `SCOUNDREL_*` variables that aren't options,
+    /// which are skipped rather than refused: the environment is shared
+    /// with whatever else the user runs.
+    fn load(cli: Vec<String>) -> Result<(Self, Vec<String>), String> {
+        let mut config = Vec::new();
+        let mut config_prefix = String::new();
+        if let Some(path) = storage::config_path()
+            && let Some(text) = storage::read(&path, "read config").map_err(|e| e.to_string())?
+        {
+            config_prefix = format!("{}: ", path.display());
+            config = text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| match line.split_once('=') {
+                    Some((key, value)) => Ok((key.trim().to_string(), value.trim().to_string())),
+                    None => Err(format!("expected key = value, got {}", line)),
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("{}{}", config_prefix, err))?;
+        }
+        let mut vars: Vec<(String, String)> = std::env::vars()
+            .filter(|(key, _)| key.starts_with("SCOUNDREL_"))
+            .collect();
+        vars.sort();
+        Settings::layer(config, &config_prefix, vars, cli)
+    }
+
+    /// `load` once the sources are read: config `key = value` pairs (with
+    /// the prefix its errors get), `SCOUNDREL_*` variables and the command line.
+    fn layer(
+        mut config: Vec<(String, String)>,
+        config_prefix: &str,
+        mut vars: Vec<(String, String)>,
+        mut cli: Vec<String>,
+    ) -> Result<(Self, Vec<String>), String> {
+        // The preset goes first, whichever source names it, so a rule value
+        // from any source overrides it. The last source to name one wins.
+        let mut difficulty = None;
+        if let Some((_, name)) = config.iter().rfind(|(key, _)| key.trim_start_matches('-') == "difficulty") {
+            difficulty = Some((name.clone(), config_prefix.to_string()));
+        }
+        config.retain(|(key, _)| key.trim_start_matches('-') != "difficulty");
+        if let Some(i) = vars.iter().position(|(key, _)| key == "SCOUNDREL_DIFFICULTY") {
+            let (key, name) = vars.remove(i);
+            difficulty = Some((name, format!("In {}: ", key)));
+        }
+        while let Some(i) = cli.iter().position(|arg| arg == "--difficulty") {
+            cli.remove(i);
+            let name = (i < cli.len()).then(|| cli.remove(i)).ok_or("--difficulty needs a value")?;
+            difficulty = Some((name, String::new()));
+        }
+        let mut settings = Settings::default();
+        if let Some((name, prefix)) = difficulty {
+            let difficulty = Difficulty::from_name(&name)
+                .ok_or_else(|| format!("{}Unknown difficulty: {}", prefix, name))?;
+            settings.apply_preset(difficulty);
+        }
+
+        settings
+            .apply_pairs(config.iter().map(|(key, value)| (key.as_str(), value.clone())))
+            .map_err(|err| format!("{}{}", config_prefix, err))?;
+        let mut warnings = Vec::new();
+        for (key, value) in vars {
+            let name = key["SCOUNDREL_".len()..].to_lowercase().replace('_', "-");
+            match settings.apply_pairs([(name.as_str(), value)]) {
+                Err(err) if err == format!("Unknown option: --{}", name) => {
+                    warnings.push(format!("Ignored {}: no such option", key));
+                }
+                Err(err) => return Err(format!("In {}: {}", key, err)),
+                Ok(()) => {}
+            }
+        }
+
+        settings.apply_args(cli)?;
+        if settings.ghost && settings.seed.is_none() {
+            return Err("--ghost needs --seed to know which run to compare against".to_string());
+        }
+        if let Some(weapon) = settings.start_weapon
+            && weapon.rank > settings.red_max_rank
+        {
+            return Err(format!(
+                "--start-weapon {} isn't in a deck whose red cards stop at {}",
+                weapon.display(),
+                settings.red_max_rank
+            ));
+        }
+        if settings.emit_events.as_deref() == Some("-") && !settings.simulate {
+            return Err("--emit-events - needs --simulate; the board is drawn on stdout".to_string());
+        }
+        if settings.log_json.as_deref() == Some("-") && !settings.simulate {
+            return Err("--log-json - needs --simulate; the board is drawn on stdout".to_string());
+        }
+        if settings.solve.is_some() && settings.skip_order == SkipOrder::Shuffle {
+            return Err("--solve needs a fixed --skip-order; shuffled skips aren't known ahead".to_string());
+        }
+        if settings.ironman {
+            let banned = [("--safe", settings.safe), ("--mulligan", settings.mulligan), ("--debug", settings.debug)];
+            if let Some((flag, _)) = banned.iter().find(|(_, on)| *on) {
+                return Err(format!("--ironman can't be combined with {}", flag));
+            }
+        }
+        if settings.safe {
+            // Safe mode turns these on whatever else asked for them off
+            settings.confirm_fights = true;
+            settings.confirm_quit = true;
+            settings.confirm_endgame_skip = true;
+        }
+        Ok((settings, warnings))
+    }
+
+    /// Options written as `name = value` (config) or `SCOUNDREL_NAME=value`
+    /// (environment). On/off options take 1/0, true/false, on/off or yes/no;
+    /// everything else goes through the same parsing as its flag.
+    fn apply_pairs<'a>(
+        &mut self,
+        pairs: impl IntoIterator<Item = (&'a str, String)>,
+    ) -> Result<(), String> {
+        let mut args = Vec::new();
+        for (key, value) in pairs {
+            // The theme is one on/off switch for now, so name it after that
+            let (key, value) = match (key, value.as_str()) {
+                ("theme", "minimal") => ("minimal", "on".to_string()),
+                ("theme", "standard") => ("minimal", "off".to_string()),
+                ("theme", other) => return Err(format!("Unknown theme: {}", other)),
+                _ => (key, value),
+            };
+            let flag = format!("--{}", key.trim_start_matches('-'));
+            let on = match value.as_str() {
+                "1" | "true" | "on" | "yes" => Some(true),
+                "0" | "false" | "off" | "no" => Some(false),
+                _ => None,
+            };
+            match on {
+                Some(on) if self.set_switch(&flag, on) => {}
+                _ => args.extend([flag, value]),
+            }
+        }
+        self.apply_args(args)
+    }
+
+    /// Turn an on/off option on or off; false if `flag` isn't one.
+    fn set_switch(&mut self, flag: &str, on: bool) -> bool {
+        match flag {
+            "--simulate" => self.simulate = on,
+            "--ghost" => self.ghost = on,
+            "--threat-colors" => self.threat_colors = on,
+            "--equip-hints" => self.equip_hints = on,
+            "--effects" => self.effects = on,
+            "--no-animations" => self.animations = !on,
+            "--deal-animation" => self.deal_animation = on,
+            "--no-carry" => self.carry = !on,
+            "--auto-advance" => self.auto_advance = on,
+            "--minimap" => self.minimap = on,
+            "--turn-summary" => self.turn_summary = on,
+            "--assist" => self.assist = on,
+            "--confirm-quit" => self.confirm_quit = on,
+            "--confirm-endgame-skip" => self.confirm_endgame_skip = on,
+            "--no-confirm-endgame-skip" => self.confirm_endgame_skip = !on,
+            "--autosave" => self.autosave = on,
+            "--wrap-navigation" => self.wrap_navigation = on,
+            "--no-wrap-navigation" => self.wrap_navigation = !on,
+            "--hold-repeat" => self.hold_repeat = on,
+            "--altscreen" => self.altscreen = on,
+            "--no-altscreen" => self.altscreen = !on,
+            "--confirm-fights" => self.confirm_fights = on,
+            "--two-weapons" => self.two_weapons = on,
+            "--mulligan" => self.mulligan = on,
+            "--ironman" => self.ironman = on,
+            "--flavor" => self.flavor = on,
+            "--no-flavor" => self.flavor = !on,
+            "--lethal-warning" => self.lethal_warning = on,
+            "--safe" => self.safe = on,
+            "--expanded-controls" => self.expanded_controls = on,
+            "--debug" => self.debug = on,
+            "--no-confirm-quit" => self.confirm_quit = !on,
+            "--ascii" => self.ascii = on,
+            "--numeric-ranks" => self.numeric_ranks = on,
+            "--minimal" => self.theme = if on { Theme::Minimal } else { Theme::Standard },
+            _ => return false,
+        }
+        true
+    }
+
+    /// Copy the rule fields of a difficulty preset, keeping everything else.
+    fn apply_preset(&mut self, difficulty: Difficulty) {
+        let preset = Settings::preset(difficulty);
+        self.difficulty = difficulty;
+        self.max_health = preset.max_health;
+        self.durability = preset.durability;
+        self.potions = preset.potions;
+        self.red_max_rank = preset.red_max_rank;
+    }
+
+    fn apply_args(&mut self, args: Vec<String>) -> Result<(), String> {
+        // The preset goes first so individual rule flags can override it
+        if let Some(i) = args.iter().position(|a| a == "--difficulty") {
+            let name = args.get(i + 1).ok_or("--difficulty needs a value")?;
+            let difficulty = Difficulty::from_name(name)
+                .ok_or_else(|| format!("Unknown difficulty: {}", name))?;
+            self.apply_preset(difficulty);
+        }
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--difficulty" => {
+                    args.next();
+                }
+                "--max-hp" => {
+                    let value = args.next().ok_or("--max-hp needs a value")?;
+                    self.max_health = value
+                        .parse()
+                        .ok()
+                        .filter(|&hp| hp > 0)
+                        .ok_or_else(|| format!("Invalid max HP: {}", value))?;
+                }
+                "--durability" => {
+                    let value = args.next().ok_or("--durability needs a value")?;
+                    self.durability = Durability::from_name(&value)
+                        .ok_or_else(|| format!("Unknown durability rule: {}", value))?;
+                }
+                "--potions" => {
+                    let value = args.next().ok_or("--potions needs a value")?;
+                    self.potions = PotionRule::from_name(&value)
+                        .ok_or_else(|| format!("Unknown potion rule: {}", value))?;
+                }
+                "--red-cards" => {
+                    let value = args.next().ok_or("--red-cards needs a value")?;
+                    self.red_max_rank = value
+                        .parse()
+                        .ok()
+                        .filter(|r| (2..=10).contains(r))
+                        .ok_or_else(|| format!("Invalid red card rank: {}", value))?;
+                }
+                "--classic-bonus" => {
+                    let value = args.next().ok_or("--classic-bonus needs a value")?;
+                    self.classic_bonus = match value.as_str() {
+                        "on" => true,
+                        "off" => false,
+                        _ => return Err(format!("--classic-bonus takes on or off, not {}", value)),
+                    };
+                }
+                "--skip-penalty" => {
+                    let value = args.next().ok_or("--skip-penalty needs a value")?;
+                    self.skip_penalty = SkipPenalty::from_name(&value)
+                        .ok_or_else(|| format!("Unknown skip penalty: {}", value))?;
+                }
+                "--skip-order" => {
+                    let value = args.next().ok_or("--skip-order needs a value")?;
+                    self.skip_order = SkipOrder::from_name(&value)
+                        .ok_or_else(|| format!("Unknown skip order: {}", value))?;
+                }
+                "--banner" => {
+                    let value = args.next().ok_or("--banner needs a value")?;
+                    self.banner = Banner::from_name(&value)
+                        .ok_or_else(|| format!("Unknown banner: {}", value))?;
+                }
+                "--card-border" => {
+                    let value = args.next().ok_or("--card-border needs a value")?;
+                    self.card_border = CardBorder::from_name(&value)
+                        .ok_or_else(|| format!("Unknown card border: {}", value))?;
+                }
+                "--keys" => {
+                    let value = args.next().ok_or("--keys needs a value")?;
+                    self.keys = KeyMap::from_name(&value)
+                        .ok_or_else(|| format!("Unknown key map: {}", value))?;
+                }
+                "--colors" => {
+                    let value = args.next().ok_or("--colors needs a value")?;
+                    self.colors = match value.as_str() {
+                        "auto" => None,
+                        _ => Some(Palette::from_name(&value).ok_or_else(|| format!("Unknown colors: {}", value))?),
+                    };
+                }
+                "--combat" => {
+                    let value = args.next().ok_or("--combat needs a value")?;
+                    self.combat = CombatFormula::from_name(&value)
+                        .ok_or_else(|| format!("Unknown combat formula: {}", value))?;
+                }
+                "--start-weapon" => {
+                    let value = args.next().ok_or("--start-weapon needs a card, e.g. D8")?;
+                    let card = Card::parse(&value)
+                        .filter(|c| c.is_weapon())
+                        .ok_or_else(|| format!("--start-weapon takes a diamond from D2 to D10, not {}", value))?;
+                    self.start_weapon = Some(card);
+                }
+                "--emit-events" => {
+                    let value = args.next().ok_or("--emit-events needs a path (or -)")?;
+                    self.emit_events = Some(value);
+                }
+                "--leaderboard" => {
+                    let value = args.next().ok_or("--leaderboard needs a file")?;
+                    self.leaderboard = Some(value);
+                }
+                "--log-json" => {
+                    let value = args.next().ok_or("--log-json needs a path (or -)")?;
+                    self.log_json = Some(value);
+                }
+                "--seed" => {
+                    let value = args.next().ok_or("--seed needs a value")?;
+                    let seed = value.parse().map_err(|_| format!("Invalid seed: {}", value))?;
+                    self.seed = Some(seed);
+                }
+                "--games" => {
+                    let value = args.next().ok_or("--games needs a value")?;
+                    self.games = value
+                        .parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| format!("Invalid game count: {}", value))?;
+                }
+                "--solve" => {
+                    let value = args.next().ok_or("--solve needs a seed")?;
+                    let seed = value.parse().map_err(|_| format!("Invalid seed: {}", value))?;
+                    self.solve = Some(seed);
+                }
+                "--log-limit" => {
+                    let value = args.next().ok_or("--log-limit needs a value")?;
+                    self.log_limit = value
+                        .parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| format!("Invalid log limit: {}", value))?;
+                }
+                "--message-timeout" => {
+                    let value = args.next().ok_or("--message-timeout needs a value")?;
+                    self.message_timeout = value
+                        .parse()
+                        .map_err(|_| format!("Invalid message timeout: {}", value))?;
+                }
+                "--puzzle" => {
+                    let value = args.next().ok_or("--puzzle needs a code")?;
+                    self.puzzle = Some(decode_puzzle(&value)?);
+                }
+                other if self.set_switch(other, true) => {}
+                other => return Err(format!("Unknown option: {}", other)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Plays that finish a room: 3 with the 4th carried, all 4 without.
+    fn plays_per_room(&self) -> u8 {
+        if self.carry { 3 } else { 4 }
+    }
+
+    /// 26 monsters plus potions and weapons from 2 up to the red cap.
+    fn deck_size(&self) -> usize {
+        26 + 2 * (self.red_max_rank as usize - 1)
+    }
+
+    /// One-line summary of the rules in effect, shown under the title.
+    fn ruleset(&self) -> String {
+        let mut rules = format!(
+            "{} · {} HP · {} · {} · red 2-{}",
+            self.difficulty.name(),
+            self.max_health,
+            self.durability.name(),
+            self.potions.name(),
+            self.red_max_rank
+        );
+        if !self.classic_bonus {
+            rules.push_str(" · no potion bonus");
+        }
+        if !self.carry {
+            rules.push_str(" · no carry");
+        }
+        if self.two_weapons {
+            rules.push_str(" · two weapons");
+        }
+        if self.safe {
+            rules.push_str(" · safe mode");
+        }
+        if self.debug {
+            rules.push_str(" · debug");
+        }
+        if self.ironman {
+            rules.push_str(" · ironman");
+        }
+        if self.combat != CombatFormula::Classic {
+            rules.push_str(" · ");
+            rules.push_str(self.combat.name());
+        }
+        if self.skip_penalty != SkipPenalty::Off {
+            rules.push_str(" · ");
+            rules.push_str(self.skip_penalty.name());
+        }
+        if self.skip_order != SkipOrder::Bottom {
+            rules.push_str(" · ");
+            rules.push_str(self.skip_order.name());
+        }
+        rules
+    }
+}
+
+/// One character per card, so a shared dungeon is as short as the deck.
+const PUZZLE_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// A dungeon order as a code that replays identically on any build, unlike
+/// a seed, which depends on the RNG.
+fn encode_puzzle(cards: &[Card]) -> String {
+    cards
+        .iter()
+        .map(|c| PUZZLE_ALPHABET[c.index()] as char)
+        .collect()
+}
+
+fn decode_puzzle(code: &str) -> Result<Vec<Card>, String> {
+    let mut cards: Vec<Card> = Vec::new();
+    for ch in code.trim().chars() {
+        let card = PUZZLE_ALPHABET
+            .iter()
+            .position(|&b| b as char == ch)
+            .and_then(Card::from_index)
+            .ok_or_else(|| format!("Invalid puzzle character: {}", ch))?;
+        if cards.iter().any(|c| c.index() == card.index()) {
+            return Err(format!("Puzzle repeats {}", card.display()));
+        }
+        cards.push(card);
+    }
+    if cards.is_empty() {
+        return Err("Puzzle code is empty".to_string());
+    }
+    Ok(cards)
+}
+
+/// A finished run on a given seed, kept so the next attempt can race it.
+struct Replay {
+    seed: u64,
+    hp_by_turn: Vec<i32>, // HP at the start of each turn
+}
+
+impl Replay {
+    /// The last run on `seed`, if there is one. A file that doesn't parse
+    /// counts as no run.
+    fn load(seed: u64) -> Result<Option<Replay>, StorageError> {
+        let Some(path) = storage::replay_path(seed) else {
+            return Ok(None);
+        };
+        let Some(text) = storage::read(&path, "load replay")? else {
+            return Ok(None);
+        };
+        Ok(Replay::parse(&text).map(|hp_by_turn| Replay { seed, hp_by_turn }))
+    }
+
+    /// The HP line of a saved replay, or `None` if it doesn't parse.
+    fn parse(text: &str) -> Option<Vec<i32>> {
+        let mut hp_by_turn = Vec::new();
+        for line in text.lines() {
+            if let Some(values) = line.strip_prefix("hp ") {
+                for v in values.split_whitespace() {
+                    hp_by_turn.push(v.parse().ok()?);
+                }
+            }
+        }
+        Some(hp_by_turn)
+    }
+
+    /// Average HP at the start of each turn over every saved run (the last
+    /// on each seed), counting only the runs that got that far, and how
+    /// many runs there were.
+    fn average_hp() -> Result<Option<(Vec<f32>, usize)>, StorageError> {
+        let Some(dir) = storage::replays_dir() else {
+            return Ok(None);
+        };
+        let runs: Vec<Vec<i32>> = storage::read_all(&dir, "load replays")?
+            .iter()
+            .filter_map(|text| Replay::parse(text))
+            .filter(|hp| !hp.is_empty())
+            .collect();
+        let turns = runs.iter().map(|hp| hp.len()).max().unwrap_or(0);
+        let average = (0..turns)
+            .map(|turn| {
+                let reached: Vec<i32> = runs.iter().filter_map(|hp| hp.get(turn).copied()).collect();
+                reached.iter().sum::<i32>() as f32 / reached.len() as f32
+            })
+            .collect();
+        Ok((!runs.is_empty()).then_some((average, runs.len())))
+    }
+
+    fn save(&self) -> Result<(), StorageError> {
+        let hp: Vec<String> = self.hp_by_turn.iter().map(|h| h.to_string()).collect();
+        let text = format!("seed {}\nhp {}\n", self.seed, hp.join(" "));
+        let path = storage::replay_path(self.seed).ok_or(StorageError::no_home("save replay"))?;
+        storage::write(&path, &text, "save replay")
+    }
+}
+
+/// An `--autosave` snapshot of a run in progress, taken at each room
+/// boundary: the deck as dealt and every move since. Replaying the moves
+/// rebuilds the whole game, log and stats included.
+struct Checkpoint {
+    seed: u64,
+    rules: String, // must match the current rules to resume
+    deck: Vec<Card>,
+    moves: Vec<Action>,
+    turn: u32,
+    summary: Option<SaveSummary>, // None in checkpoints written before it existed
+}
+
+/// Where a checkpointed run stands, saved beside its moves so the Resume
+/// prompt can show it without replaying them.
+struct SaveSummary {
+    health: i32,
+    max_health: i32,
+    weapon: Option<Weapon>,
+    dungeon: usize, // cards still to be dealt
+}
+
+impl SaveSummary {
+    fn of(game: &GameState) -> Self {
+        SaveSummary {
+            health: game.health,
+            max_health: game.max_health,
+            weapon: game.weapon.clone(),
+            dungeon: game.dungeon.len(),
+        }
+    }
+
+    /// The checkpoint file's lines for it: `health 14/20`, `dungeon 28`,
+    /// and `weapon 7 9` (rank, then the last monster slain or `-`) if armed.
+    fn encode(&self) -> String {
+        let mut text = format!("health {}/{}\ndungeon {}\n", self.health, self.max_health, self.dungeon);
+        if let Some(ref weapon) = self.weapon {
+            let slain = weapon.last_monster_slain.map_or("-".to_string(), |v| v.to_string());
+            text.push_str(&format!("weapon {} {}\n", weapon.card.rank, slain));
+        }
+        text
+    }
+
+    fn decode(fields: &HashMap<&str, &str>) -> Option<Self> {
+        let (health, max_health) = fields.get("health")?.split_once('/')?;
+        let weapon = match fields.get("weapon") {
+            Some(value) => {
+                let (rank, slain) = value.split_once(' ')?;
+                Some(Weapon {
+                    card: Card { suit: Suit::Diamonds, rank: rank.parse().ok()? },
+                    last_monster_slain: if slain == "-" { None } else { Some(slain.parse().ok()?) },
+                })
+            }
+            None => None,
+        };
+        Some(SaveSummary {
+            health: health.parse().ok()?,
+            max_health: max_health.parse().ok()?,
+            weapon,
+            dungeon: fields.get("dungeon")?.parse().ok()?,
+        })
+    }
+
+    /// e.g. "14/20 HP · 7♦ (last slew 9) · 28 cards to go".
+    fn describe(&self) -> String {
+        let weapon = match self.weapon {
+            Some(ref w) => match w.last_monster_slain {
+                Some(slain) => format!("{} (last slew {})", w.card.display(), slain),
+                None => format!("{} (fresh)", w.card.display()),
+            },
+            None => "unarmed".to_string(),
+        };
+        format!(
+            "{}/{} HP · {} · {} cards to go",
+            self.health, self.max_health, weapon, self.dungeon
+        )
+    }
+}
+
+impl Checkpoint {
+    /// What a resume has to agree on: the rule set, plus any starting weapon.
+    fn rules_of(settings: &Settings) -> String {
+        match settings.start_weapon {
+            Some(card) => format!("{} · start D{}", settings.ruleset(), card.rank),
+            None => settings.ruleset(),
+        }
+    }
+
+    fn encode_move(action: Action) -> String {
+        match action {
+            Action::PlayPotion(index) => format!("p{}", index),
+            Action::PlayWeapon(index) => format!("w{}", index),
+            Action::Fight { index, use_weapon: false } => format!("f{}", index),
+            Action::Fight { index, use_weapon: true } => format!("F{}", index),
+            Action::FightWithSpare(index) => format!("x{}", index),
+            Action::Skip => "s".to_string(),
+        }
+    }
+
+    fn decode_move(code: &str) -> Option<Action> {
+        if code == "s" {
+            return Some(Action::Skip);
+        }
+        let (kind, index) = code.split_at_checked(1)?;
+        let index = index.parse().ok()?;
+        match kind {
+            "p" => Some(Action::PlayPotion(index)),
+            "w" => Some(Action::PlayWeapon(index)),
+            "f" => Some(Action::Fight { index, use_weapon: false }),
+            "F" => Some(Action::Fight { index, use_weapon: true }),
+            "x" => Some(Action::FightWithSpare(index)),
+            _ => None,
+        }
+    }
+
+    /// The saved checkpoint, if any. A file that doesn't parse counts as none.
+    fn load() -> Result<Option<Checkpoint>, StorageError> {
+        let Some(path) = storage::checkpoint_path() else {
+            return Ok(None);
+        };
+        let Some(text) = storage::read(&path, "load checkpoint")? else {
+            return Ok(None);
+        };
+        let mut fields = HashMap::new();
+        for line in text.lines() {
+            if let Some((key, value)) = line.split_once(' ') {
+                fields.insert(key, value);
+            }
+        }
+        let parsed = || -> Option<Checkpoint> {
+            let moves = fields.get("moves").copied().unwrap_or("");
+            Some(Checkpoint {
+                seed: fields.get("seed")?.parse().ok()?,
+                rules: fields.get("rules")?.to_string(),
+                deck: decode_puzzle(fields.get("deck")?).ok()?,
+                moves: moves.split_whitespace().map(Checkpoint::decode_move).collect::<Option<_>>()?,
+                turn: fields.get("turn")?.parse().ok()?,
+                summary: SaveSummary::decode(&fields),
+            })
+        };
+        Ok(parsed())
+    }
+
+    fn save(&self) -> Result<(), StorageError> {
+        let moves: Vec<String> = self.moves.iter().map(|&a| Checkpoint::encode_move(a)).collect();
+        let text = format!(
+            "seed {}\nrules {}\nturn {}\n{}deck {}\nmoves {}\n",
+            self.seed,
+            self.rules,
+            self.turn,
+            self.summary.as_ref().map_or(String::new(), SaveSummary::encode),
+            encode_puzzle(&self.deck),
+            moves.join(" ")
+        );
+        let path = storage::checkpoint_path().ok_or(StorageError::no_home("save checkpoint"))?;
+        storage::replace(&path, &text, "save checkpoint")
+    }
+
+    fn clear() -> Result<(), StorageError> {
+        match storage::checkpoint_path() {
+            Some(path) => storage::remove(&path, "clear checkpoint"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A run on a `--leaderboard` file: one tab-separated line of name, score,
+/// result, seed and rules, appended under a lock so several instances can
+/// share the file.
+#[derive(Clone)]
+struct LeaderboardEntry {
+    name: String,
+    score: i32,
+    won: bool,
+    seed: u64,
+    rules: String, // only runs under the same rules are ranked together
+}
+
+/// How many entries the game-over screen lists.
+const LEADERBOARD_SHOWN: usize = 5;
+
+/// The longest name the prompt takes.
+const LEADERBOARD_NAME_LEN: usize = 12;
+
+impl LeaderboardEntry {
+    fn encode(&self) -> String {
+        let result = if self.won { "won" } else { "died" };
+        format!("{}\t{}\t{}\t{}\t{}", self.name, self.score, result, self.seed, self.rules)
+    }
+
+    fn decode(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        Some(LeaderboardEntry {
+            name: fields.next()?.to_string(),
+            score: fields.next()?.parse().ok()?,
+            won: match fields.next()? {
+                "won" => true,
+                "died" => false,
+                _ => return None,
+            },
+            seed: fields.next()?.parse().ok()?,
+            rules: fields.next()?.to_string(),
+        })
+    }
+
+    /// The file's runs under `rules`, best score first; of equal scores,
+    /// the one added first ranks higher. Lines that don't parse are skipped.
+    fn load(path: &Path, rules: &str) -> Result<Vec<LeaderboardEntry>, StorageError> {
+        let text = storage::read_locked(path, "load leaderboard")?.unwrap_or_default();
+        let mut entries: Vec<LeaderboardEntry> = text
+            .lines()
+            .filter_map(LeaderboardEntry::decode)
+            .filter(|entry| entry.rules == rules)
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        Ok(entries)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), StorageError> {
+        storage::append_locked(path, &self.encode(), "add to leaderboard")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum LogKind {
+    Room,
+    Combat,
+    Weapon,
+    Potion,
+    System,
+}
+
+/// Something worth remembering about the run. The log view's lines and
+/// the `--log-json` stream are both written from these.
+enum LogEvent {
+    Entered { health: i32, seed: u64 },
+    Difficulty(String),
+    StartWeapon(Card),
+    RoomEntered(Vec<Card>),
+    RoomSkipped(Vec<Card>),
+    SkipPaidHealth(i32),
+    SkipCostsScore(i32),
+    PotionWasted(Card),
+    PotionDrunk { card: Card, healed: i32, health: i32 },
+    WeaponEquipped(Card),
+    WeaponReplaced { old: Card, new: Card },
+    WeaponSpared { new: Card, spare: Card }, // --two-weapons
+    KilledWithWeapon { monster: Card, weapon: Card, damage: i32, health: i32 },
+    FoughtBarehanded { monster: Card, damage: i32, health: i32 },
+    Died,
+    Victory { score: i32 },
+    Undone(String), // --safe: the move taken back, as describe_action puts it
+    Mulligan(u64),  // the seed given up for a redeal
+    Debug(String),  // a --debug command that was run
+    Warning(String), // storage and stream failures
+}
+
+impl LogEvent {
+    fn kind(&self) -> LogKind {
+        match self {
+            LogEvent::Entered { .. }
+            | LogEvent::Difficulty(_)
+            | LogEvent::RoomEntered(_)
+            | LogEvent::RoomSkipped(_)
+            | LogEvent::SkipPaidHealth(_)
+            | LogEvent::SkipCostsScore(_)
+            | LogEvent::Victory { .. } => LogKind::Room,
+            LogEvent::StartWeapon(_)
+            | LogEvent::WeaponEquipped(_)
+            | LogEvent::WeaponReplaced { .. }
+            | LogEvent::WeaponSpared { .. } => LogKind::Weapon,
+            LogEvent::PotionWasted(_) | LogEvent::PotionDrunk { .. } => LogKind::Potion,
+            LogEvent::KilledWithWeapon { .. } | LogEvent::FoughtBarehanded { .. } | LogEvent::Died => {
+                LogKind::Combat
+            }
+            LogEvent::Undone(_) | LogEvent::Mulligan(_) | LogEvent::Debug(_) | LogEvent::Warning(_) => {
+                LogKind::System
+            }
+        }
+    }
+
+    /// The line the log view shows, without the turn.
+    fn text(&self) -> String {
+        let cards = |cards: &[Card]| cards.iter().map(|c| c.display()).collect::<Vec<_>>().join(", ");
+        match self {
+            LogEvent::Entered { health, seed } => {
+                format!("Entered the dungeon with {} HP (seed {})", health, seed)
+            }
+            LogEvent::Difficulty(rating) => format!("Dungeon difficulty: {}", rating),
+            LogEvent::StartWeapon(card) => format!("Started with {}", card.display()),
+            LogEvent::RoomEntered(room) => format!("Entered room: {}", cards(room)),
+            LogEvent::RoomSkipped(room) => format!("Skipped room ({})", cards(room)),
+            LogEvent::SkipPaidHealth(hp) => format!("Paid {} HP to skip", hp),
+            LogEvent::SkipCostsScore(points) => format!("Skip will cost {} point", points),
+            LogEvent::PotionWasted(card) => format!("Wasted {} (already used potion)", card.display()),
+            LogEvent::PotionDrunk { card, healed, health } => {
+                format!("Drank {}, healed {} HP (now {} HP)", card.display(), healed, health)
+            }
+            LogEvent::WeaponEquipped(card) => format!("Equipped {}", card.display()),
+            LogEvent::WeaponReplaced { old, new } => {
+                format!("Discarded {}, equipped {}", old.display(), new.display())
+            }
+            LogEvent::WeaponSpared { new, spare } => {
+                format!("Equipped {}, keeping {} as a spare", new.display(), spare.display())
+            }
+            LogEvent::KilledWithWeapon { monster, weapon, damage, health } => format!(
+                "Killed {} with {}, took {} dmg (now {} HP)",
+                monster.display(),
+                weapon.display(),
+                damage,
+                health
+            ),
+            LogEvent::FoughtBarehanded { monster, damage, health } => {
+                let monster = monster.display();
+                format!("Fought {} barehanded, took {} dmg (now {} HP)", monster, damage, health)
+            }
+            LogEvent::Died => "DIED!".to_string(),
+            LogEvent::Victory { score } => format!("VICTORY! Score: {}", score),
+            LogEvent::Undone(action) => format!("Took back: {}", action),
+            LogEvent::Mulligan(seed) => format!("Took a mulligan, giving up seed {}", seed),
+            LogEvent::Debug(command) => format!("Debug: {}", command),
+            LogEvent::Warning(text) => text.clone(),
+        }
+    }
+
+    /// One JSON object, without the trailing newline; fields as in `GameEvent::to_json`.
+    fn to_json(&self, turn: u32) -> String {
+        let card = |card: &Card| format!("\"{}\"", card.display());
+        let cards = |cards: &[Card]| {
+            let quoted: Vec<String> = cards.iter().map(card).collect();
+            format!("[{}]", quoted.join(","))
+        };
+        let body = match self {
+            LogEvent::Entered { health, seed } => {
+                format!("\"entered\",\"health\":{},\"seed\":{}", health, seed)
+            }
+            LogEvent::Difficulty(rating) => format!("\"difficulty\",\"rating\":{}", json_string(rating)),
+            LogEvent::StartWeapon(weapon) => format!("\"start_weapon\",\"card\":{}", card(weapon)),
+            LogEvent::RoomEntered(room) => format!("\"room_entered\",\"cards\":{}", cards(room)),
+            LogEvent::RoomSkipped(room) => format!("\"room_skipped\",\"cards\":{}", cards(room)),
+            LogEvent::SkipPaidHealth(hp) => format!("\"skip_paid_health\",\"amount\":{}", hp),
+            LogEvent::SkipCostsScore(points) => format!("\"skip_costs_score\",\"amount\":{}", points),
+            LogEvent::PotionWasted(potion) => format!("\"potion_wasted\",\"card\":{}", card(potion)),
+            LogEvent::PotionDrunk { card: potion, healed, health } => format!(
+                "\"potion_drunk\",\"card\":{},\"healed\":{},\"health\":{}",
+                card(potion),
+                healed,
+                health
+            ),
+            LogEvent::WeaponEquipped(weapon) => format!("\"weapon_equipped\",\"card\":{}", card(weapon)),
+            LogEvent::WeaponReplaced { old, new } => {
+                format!("\"weapon_replaced\",\"old\":{},\"new\":{}", card(old), card(new))
+            }
+            LogEvent::WeaponSpared { new, spare } => {
+                format!("\"weapon_spared\",\"new\":{},\"spare\":{}", card(new), card(spare))
+            }
+            LogEvent::KilledWithWeapon { monster, weapon, damage, health } => format!(
+                "\"killed_with_weapon\",\"monster\":{},\"weapon\":{},\"damage\":{},\"health\":{}",
+                card(monster),
+                card(weapon),
+                damage,
+                health
+            ),
+            LogEvent::FoughtBarehanded { monster, damage, health } => format!(
+                "\"fought_barehanded\",\"monster\":{},\"damage\":{},\"health\":{}",
+                card(monster),
+                damage,
+                health
+            ),
+            LogEvent::Died => "\"died\"".to_string(),
+            LogEvent::Victory { score } => format!("\"victory\",\"score\":{}", score),
+            LogEvent::Undone(action) => format!("\"undone\",\"action\":{}", json_string(action)),
+            LogEvent::Mulligan(seed) => format!("\"mulligan\",\"old_seed\":{}", seed),
+            LogEvent::Debug(command) => format!("\"debug\",\"command\":{}", json_string(command)),
+            LogEvent::Warning(text) => format!("\"warning\",\"text\":{}", json_string(text)),
+        };
+        format!("{{\"event\":{},\"turn\":{}}}", body, turn)
+    }
+}
+
+/// A JSON string literal, quotes included.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+struct LogEntry {
+    turn: u32,
+    event: LogEvent,
+}
+
+impl LogEntry {
+    fn kind(&self) -> LogKind {
+        self.event.kind()
+    }
+
+    fn text(&self) -> String {
+        format!("[Turn {}] {}", self.turn, self.event.text())
+    }
+}
+
+/// Which log entries the log view shows.
+#[derive(Clone, Copy, PartialEq)]
+enum LogFilter {
+    All,
+    Combat, // fights and weapon changes
+    Potions,
+}
+
+impl LogFilter {
+    fn matches(&self, kind: LogKind) -> bool {
+        match self {
+            LogFilter::All => true,
+            LogFilter::Combat => matches!(kind, LogKind::Combat | LogKind::Weapon),
+            LogFilter::Potions => kind == LogKind::Potion,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            LogFilter::All => "all",
+            LogFilter::Combat => "combat",
+            LogFilter::Potions => "potions",
+        }
+    }
+}
+
+/// One thing the player can do with the current room.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Action {
+    PlayPotion(usize),
+    PlayWeapon(usize),
+    Fight { index: usize, use_weapon: bool },
+    FightWithSpare(usize), // --two-weapons: draw the spare and fight with it
+    Skip,
+}
+
+/// Something observable that happened, for `--emit-events`.
+enum GameEvent {
+    RoomDealt { cards: Vec<Card>, dungeon_left: usize, state: u64 },
+    RoomSkipped { cards: Vec<Card> },
+    CardPlayed { card: Card, play: &'static str }, // potion, weapon, weapon_fight or barehanded
+    Damage { amount: i32, health: i32 },
+    Heal { amount: i32, health: i32 },
+    GameOver { won: bool, score: i32, state: u64 },
+}
+
+impl GameEvent {
+    /// One JSON object, without the trailing newline.
+    fn to_json(&self, turn: u32) -> String {
+        let cards = |cards: &[Card]| {
+            let quoted: Vec<String> = cards.iter().map(|c| format!("\"{}\"", c.display())).collect();
+            format!("[{}]", quoted.join(","))
+        };
+        let body = match self {
+            GameEvent::RoomDealt { cards: room, dungeon_left, state } => format!(
+                "\"room_dealt\",\"cards\":{},\"dungeon_left\":{},\"state\":\"{:016x}\"",
+                cards(room),
+                dungeon_left,
+                state
+            ),
+            GameEvent::RoomSkipped { cards: room } => {
+                format!("\"room_skipped\",\"cards\":{}", cards(room))
+            }
+            GameEvent::CardPlayed { card, play } => {
+                format!("\"card_played\",\"card\":\"{}\",\"play\":\"{}\"", card.display(), play)
+            }
+            GameEvent::Damage { amount, health } => {
+                format!("\"damage\",\"amount\":{},\"health\":{}", amount, health)
+            }
+            GameEvent::Heal { amount, health } => {
+                format!("\"heal\",\"amount\":{},\"health\":{}", amount, health)
+            }
+            GameEvent::GameOver { won, score, state } => {
+                format!("\"game_over\",\"won\":{},\"score\":{},\"state\":\"{:016x}\"", won, score, state)
+            }
+        };
+        format!("{{\"event\":{},\"turn\":{}}}", body, turn)
+    }
+}
+
+/// Why a move couldn't be played.
+#[derive(Debug, PartialEq)]
+enum MoveError {
+    IndexOutOfRange { index: usize, len: usize },
+    /// The action doesn't fit the card, e.g. drinking a monster.
+    WrongCardType { card: Card, expected: &'static str },
+    /// A weapon fight the chosen weapon can't make: none held, or too dull.
+    WeaponCantHit { card: Card },
+    /// A skip the rules don't allow right now, and why.
+    CannotSkip(&'static str),
+    GameOver,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::IndexOutOfRange { index, len } => {
+                write!(f, "No card in slot {} (the room has {})", index + 1, len)
+            }
+            MoveError::WrongCardType { card, expected } => {
+                write!(f, "{} is not {}", card.display(), expected)
+            }
+            MoveError::WeaponCantHit { card } => write!(f, "That weapon can't hit {}", card.display()),
+            MoveError::CannotSkip(reason) => write!(f, "{}", reason),
+            MoveError::GameOver => write!(f, "The run is over"),
+        }
+    }
+}
+
+/// How one room went, for the mini-map.
+struct RoomSummary {
+    skipped: bool,
+    damage: i32,
+    healed: i32,
+    slain: usize,
+}
+
+/// Cards left in the dungeon at or below which a skip asks first.
+const ENDGAME_CARDS: usize = 8;
+
+/// How long the room label flashes after a deadly room is dealt, in ticks.
+const DEADLY_FLASH_TICKS: u8 = 20;
+
+/// How long the room's card borders light up after a new room is dealt.
+const NEW_ROOM_FLASH_TICKS: u8 = 6;
+
+/// With `--deal-animation`, ticks between one new card showing and the next.
+const DEAL_STEP_TICKS: u8 = 2;
+
+/// How long the `--turn-summary` recap stays up unless a key dismisses it.
+const SUMMARY_TICKS: u8 = 25;
+
+/// How long a floating damage number stays up, in ticks.
+const POPUP_TICKS: u8 = 10;
+
+/// Lines the controls take with `--expanded-controls` (K) on.
+const EXPANDED_CONTROLS_LINES: usize = 4;
+
+/// With `--hold-repeat`, how long Left/Right must be held before the
+/// selection starts moving on its own, and after how long it steps every
+/// tick instead of every other one.
+const HOLD_DELAY_TICKS: u64 = 3;
+const HOLD_FAST_TICKS: u64 = 10;
+
+/// A "-N" that drifts up from the card that dealt the damage.
+struct DamagePopup {
+    amount: i32,
+    area: Rect,
+    ticks_left: u8,
+}
+
+struct GameState {
+    dungeon: Vec<Card>,
+    room: Vec<Card>,
+    discard: Vec<Card>,
+    health: i32,
+    max_health: i32,
+    weapon: Option<Weapon>,
+    monsters_on_weapon: Vec<Card>,
+    spare: Option<Weapon>, // --two-weapons only
+    monsters_on_spare: Vec<Card>,
+    weapon_history: Vec<(Card, usize)>, // replaced weapons and how many monsters each slew
+    cards_played_this_turn: u8,
+    played_this_turn: Vec<Card>, // in play order, cleared when a room is dealt
+    turn_potion: Option<u8>, // the value of the potion that counts this turn
+    just_skipped: bool,
+    game_over: bool,
+    won: bool,
+    last_card_was_potion: Option<Card>,
+    log: Vec<LogEntry>,
+    log_dropped: usize, // oldest entries let go to stay under log_limit
+    log_filter: LogFilter,
+    auto_pending: Option<Action>, // forced move waiting for a second A
+    lethal_pending: Option<Action>, // --safe: fatal fight waiting to be chosen again
+    turn_number: u32,
+    selected_index: usize,
+    screen: Screen,
+    combat_card_index: Option<usize>,
+    combat_selection: usize, // 0 = weapon, 1 = barehanded, 2 = back
+    results_scroll: u16,     // lines scrolled down the results screen
+    message: String,
+    message_shown: String, // what on_tick last saw, to notice a new message
+    message_since: u64, // tick of the last new message or key press
+    sticky_message: String, // kept past --message-timeout while it's still showing
+    card_areas: Vec<Rect>, // Store card positions for mouse clicks
+    weapon_area: Rect,     // the weapon box, which a click explains
+    combat_button_areas: Vec<Rect>, // Store combat button positions
+    settings: Settings,
+    seed: u64,
+    hp_by_turn: Vec<i32>,
+    ghost: Option<Replay>,
+    moves: u32,
+    skip_penalty: i32, // points owed for skips under SkipPenalty::Score
+    avoidable_mistakes: u32, // barehanded fights a usable weapon could have taken, wasted potions
+    show_analysis: bool,
+    damage_popup: Option<DamagePopup>,
+    ticks: u64,
+    play_ticks: u64, // ticks of the run itself, not counting pauses
+    deadly_flash: u8, // ticks of red room label left
+    new_room_flash: u8, // ticks of highlighted card borders left
+    dealing: Option<(usize, u8)>, // --deal-animation: first new slot, and ticks it's been dealing
+    flawless_room: bool, // the room just finished cost no HP; flashes green
+    summary_ticks: u8, // until the turn summary closes itself
+    initial_order: Vec<Card>,
+    kills_with_weapon: u32,
+    kills_barehanded: u32,
+    potions_drunk: u32,
+    weapons_equipped: u32,
+    persist: bool, // write replays to disk; off for simulations and --debug
+    debug_input: String, // the ` prompt being typed
+    held: Option<(bool, u64)>, // --hold-repeat: direction held (true = right) and the tick it went down
+    rooms: Vec<RoomSummary>, // every finished or skipped room, oldest first
+    room_damage: i32,        // taken so far in the current room
+    room_healed: i32,
+    bot_score: Option<i32>, // the greedy strategy's score on this dungeon, once over
+    events: Option<Box<dyn Write>>, // --emit-events stream; None for bot games
+    log_stream: Option<Box<dyn Write>>, // --log-json stream
+    history: Vec<Action>, // every move played, for --autosave
+    damage_by_suit: [i32; 2], // taken from spades, clubs
+    damage_prevented: i32, // soaked by weapons: monster value minus damage dealt
+    killing_blow: Option<(Card, bool, i32)>, // monster, with weapon, damage
+    assist_mode: bool, // I hides every assist at once; the flags say which exist
+    pending_resume: Option<Checkpoint>, // offered on the Resume screen
+    mulligan_available: bool, // --mulligan: the first room is hopeless and nothing's been played
+    mulligan_used: bool, // this run is a redeal; kept off strict leaderboards
+    average_hp: Option<(Vec<f32>, usize)>, // past runs' HP by turn, read at game over, and how many
+    leaderboard: Vec<LeaderboardEntry>, // --leaderboard: the top runs under these rules, read at game over
+    leaderboard_name: Option<String>,   // being typed to add this run; None once added or declined
+    leaderboard_place: Option<usize>,   // where this run landed, counting from 1
+}
+
+/// The generator behind every seeded deal. ChaCha12 is named outright
+/// rather than through `StdRng`, which rand may switch to another algorithm
+/// in any release, and the seed is expanded by hand (PCG32, as
+/// `seed_from_u64` does today) so a seed deals the same dungeon on every
+/// build.
+fn seeded_rng(mut state: u64) -> ChaCha12Rng {
+    const MUL: u64 = 6364136223846793005;
+    const INC: u64 = 11634580027462260723;
+    let mut seed = [0u8; 32];
+    for chunk in seed.chunks_exact_mut(4) {
+        state = state.wrapping_mul(MUL).wrapping_add(INC);
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        let word = xorshifted.rotate_right((state >> 59) as u32);
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    ChaCha12Rng::from_seed(seed)
+}
+
+/// A uniform index below `n`, by widening multiply with rejection; the
+/// same draws rand 0.8's `gen_range` makes, but fixed here.
+fn below(rng: &mut impl RngCore, n: usize) -> usize {
+    let range = n as u32;
+    let zone = (range << range.leading_zeros()).wrapping_sub(1);
+    loop {
+        let wide = rng.next_u32() as u64 * range as u64;
+        if (wide as u32) <= zone {
+            return (wide >> 32) as usize;
+        }
+    }
+}
+
+/// The deck before shuffling: ♠ 2 to A, then ♣ 2 to A, then ♥ and ♦ each
+/// from 2 up to `red_max_rank` (44 cards at the default of 10). A seed's
+/// dungeon is this order put through `shuffle`, so changing it - even just
+/// swapping two suits - deals every seed, ghost and replay differently.
+fn unshuffled_deck(red_max_rank: u8) -> Vec<Card> {
+    let mut deck = Vec::new();
+    // Black suits: full range 2-14
+    for suit in [Suit::Spades, Suit::Clubs] {
+        for rank in 2..=14 {
+            deck.push(Card { suit, rank });
+        }
+    }
+    // Red suits: only 2-10 (no face cards or aces), fewer on hard
+    for suit in [Suit::Hearts, Suit::Diamonds] {
+        for rank in 2..=red_max_rank {
+            deck.push(Card { suit, rank });
+        }
+    }
+    deck
+}
+
+/// Fisher–Yates, last card first, so the order depends only on the
+/// generator's output and not on how rand happens to shuffle.
+fn shuffle<T>(items: &mut [T], rng: &mut impl RngCore) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, below(rng, i + 1));
+    }
+}
+
+impl GameState {
+    fn new(settings: Settings) -> Self {
+        let seed = settings.seed.unwrap_or_else(|| rand::thread_rng().r#gen());
+        GameState::with_seed(settings, seed)
+    }
+
+    fn with_seed(settings: Settings, seed: u64) -> Self {
+        GameState::with_rng(settings, seed, &mut seeded_rng(seed))
+    }
+
+    /// Build a game whose shuffle comes from `rng`; `seed` is only recorded
+    /// for replays and display. Tests and simulations can pass any Rng here.
+    fn with_rng(settings: Settings, seed: u64, rng: &mut impl Rng) -> Self {
+        let (ghost, ghost_warning) = match settings.ghost.then(|| Replay::load(seed)) {
+            Some(Ok(replay)) => (replay, None),
+            Some(Err(err)) => (None, Some(err.to_string())),
+            None => (None, None),
+        };
+        let max_health = settings.max_health;
+        let persist = !settings.debug;
+        let mut state = GameState {
+            dungeon: Vec::new(),
+            room: Vec::new(),
+            discard: Vec::new(),
+            health: max_health,
+            max_health,
+            weapon: None,
+            monsters_on_weapon: Vec::new(),
+            spare: None,
+            monsters_on_spare: Vec::new(),
+            weapon_history: Vec::new(),
+            cards_played_this_turn: 0,
+            played_this_turn: Vec::new(),
+            turn_potion: None,
+            just_skipped: false,
+            game_over: false,
+            won: false,
+            last_card_was_potion: None,
+            log: Vec::new(),
+            log_dropped: 0,
+            log_filter: LogFilter::All,
+            auto_pending: None,
+            lethal_pending: None,
+            turn_number: 1,
+            selected_index: 0,
+            screen: Screen::Game,
+            combat_card_index: None,
+            combat_selection: 0,
+            results_scroll: 0,
+            message: String::new(),
+            message_shown: String::new(),
+            message_since: 0,
+            sticky_message: String::new(),
+            card_areas: Vec::new(),
+            weapon_area: Rect::default(),
+            combat_button_areas: Vec::new(),
+            settings,
+            seed,
+            hp_by_turn: vec![max_health],
+            ghost,
+            moves: 0,
+            skip_penalty: 0,
+            avoidable_mistakes: 0,
+            show_analysis: false,
+            damage_popup: None,
+            ticks: 0,
+            play_ticks: 0,
+            deadly_flash: 0,
+            new_room_flash: 0,
+            dealing: None,
+            flawless_room: false,
+            summary_ticks: 0,
+            initial_order: Vec::new(),
+            kills_with_weapon: 0,
+            kills_barehanded: 0,
+            potions_drunk: 0,
+            weapons_equipped: 0,
+            persist,
+            debug_input: String::new(),
+            held: None,
+            rooms: Vec::new(),
+            room_damage: 0,
+            room_healed: 0,
+            bot_score: None,
+            events: None,
+            log_stream: None,
+            history: Vec::new(),
+            damage_by_suit: [0; 2],
+            damage_prevented: 0,
+            killing_blow: None,
+            assist_mode: true,
+            pending_resume: None,
+            mulligan_available: false,
+            mulligan_used: false,
+            average_hp: None,
+            leaderboard: Vec::new(),
+            leaderboard_name: None,
+            leaderboard_place: None,
+        };
+        state.setup_deck(rng);
+        if let Some(card) = state.settings.start_weapon {
+            // The weapon comes out of the deck so it can't turn up twice
+            state.dungeon.retain(|c| c.index() != card.index());
+            state.initial_order.retain(|c| c.index() != card.index());
+            state.weapon = Some(Weapon {
+                card,
+                last_monster_slain: None,
+            });
+            state.weapons_equipped += 1;
+            state.log(LogEvent::StartWeapon(card));
+        }
+        state.log(LogEvent::Entered { health: max_health, seed });
+        let rating = state.difficulty_rating();
+        state.message = format!("Dungeon difficulty: {}", rating);
+        state.log(LogEvent::Difficulty(rating));
+        if let Some(warning) = ghost_warning {
+            state.message = warning;
+        } else if state.settings.ghost && state.ghost.is_none() {
+            state.message = format!("No previous run on seed {} to race", seed);
+        }
+        state.deal_room();
+        if state.settings.mulligan && state.settings.puzzle.is_none() && state.hopeless_opening() {
+            state.mulligan_available = true;
+            state.message = "No weapon and no way through this room - press M for a one-time redeal".to_string();
+        }
+        state
+    }
+
+    /// The first room is all monsters, there's no weapon to meet them, and
+    /// the cheapest way through it is fatal: only a skip gets out alive.
+    fn hopeless_opening(&self) -> bool {
+        if self.weapon.is_some() || !self.room.iter().all(|c| c.is_monster()) {
+            return false;
+        }
+        let mut values: Vec<i32> = self.room.iter().map(|c| c.value() as i32).collect();
+        values.sort();
+        values.iter().take(self.settings.plays_per_room() as usize).sum::<i32>() >= self.health
+    }
+
+    /// How hard the deal looks before a card is played, 0 (kind) to 100
+    /// (cruel), from the starting order alone: how deep the first weapon
+    /// sits (40%), how big the monsters in the first three rooms' worth of
+    /// cards are (40%), and how few potions those cards hold (20%).
+    fn dungeon_difficulty_score(&self) -> u8 {
+        let order = &self.initial_order;
+        if order.is_empty() {
+            return 0;
+        }
+        let weapon_depth = if self.settings.start_weapon.is_some() {
+            0.0
+        } else {
+            // Nothing to hold for the first three rooms is as bad as it gets
+            let first = order.iter().position(|c| c.is_weapon()).unwrap_or(order.len());
+            (first as f32 / 12.0).min(1.0)
+        };
+        let early = &order[..order.len().min(12)];
+        let monsters: Vec<f32> = early.iter().filter(|c| c.is_monster()).map(|c| c.value() as f32).collect();
+        let monster_size = if monsters.is_empty() {
+            0.0
+        } else {
+            // An average of 5 or less is easy going, 11 or more a wall
+            ((monsters.iter().sum::<f32>() / monsters.len() as f32 - 5.0) / 6.0).clamp(0.0, 1.0)
+        };
+        let potions = order.iter().filter(|c| c.is_potion()).count().max(1);
+        let early_potions = early.iter().filter(|c| c.is_potion()).count();
+        let drought = 1.0 - (early_potions as f32 / potions as f32 * 3.0).min(1.0);
+        (100.0 * (0.4 * weapon_depth + 0.4 * monster_size + 0.2 * drought)).round() as u8
+    }
+
+    /// The difficulty score with a word for it, e.g. "58/100, a rough deal".
+    fn difficulty_rating(&self) -> String {
+        let score = self.dungeon_difficulty_score();
+        let word = match score {
+            0..35 => "a kind deal",
+            35..50 => "a fair deal",
+            50..65 => "a rough deal",
+            _ => "a cruel deal",
+        };
+        format!("{}/100, {}", score, word)
+    }
+
+    fn log(&mut self, event: LogEvent) {
+        let entry = LogEntry { turn: self.turn_number, event };
+        let lost = if let Some(stream) = self.log_stream.as_mut()
+            && writeln!(stream, "{}", entry.event.to_json(entry.turn)).is_err()
+        {
+            self.log_stream = None;
+            true
+        } else {
+            false
+        };
+        self.log.push(entry);
+        if lost {
+            // Nowhere left to report it but the log itself
+            self.log(LogEvent::Warning("Couldn't write the JSON log; it stops here".to_string()));
+        }
+        // Trim in batches so a full log isn't shifted on every entry
+        let limit = self.settings.log_limit;
+        if self.log.len() > limit + limit / 10 {
+            let excess = self.log.len() - limit;
+            self.log.drain(..excess);
+            self.log_dropped += excess;
+        }
+    }
+
+    /// Carry the `--log-json` stream into a fresh game, writing out what it
+    /// has logged so far. The stream sees every entry as it's logged, so it
+    /// stays whole even once the in-memory log starts dropping old ones.
+    fn attach_log(&mut self, mut stream: Option<Box<dyn Write>>) {
+        if let Some(out) = stream.as_mut() {
+            for entry in &self.log {
+                if writeln!(out, "{}", entry.event.to_json(entry.turn)).is_err() {
+                    self.log(LogEvent::Warning("Couldn't write the JSON log; it stops here".to_string()));
+                    return;
+                }
+            }
+        }
+        self.log_stream = stream;
+    }
+
+    fn setup_deck(&mut self, rng: &mut impl Rng) {
+        if let Some(ref order) = self.settings.puzzle {
+            self.dungeon = order.clone();
+            self.initial_order = order.clone();
+            return;
+        }
+        self.dungeon = unshuffled_deck(self.settings.red_max_rank);
+        shuffle(&mut self.dungeon, rng);
+        self.initial_order = self.dungeon.clone();
+    }
+
+    fn deal_room(&mut self) {
+        let first_new = self.room.len();
+        if self.settings.deal_animation && self.settings.animations && !self.dungeon.is_empty() {
+            self.dealing = Some((first_new, 0));
+        }
+        while self.room.len() < 4 && !self.dungeon.is_empty() {
+            self.room.push(self.dungeon.remove(0));
+        }
+        self.cards_played_this_turn = 0;
+        self.played_this_turn.clear();
+        self.turn_potion = None;
+        self.last_card_was_potion = None;
+        self.selected_index = 0;
+
+        if !self.room.is_empty() {
+            self.log(LogEvent::RoomEntered(self.room.clone()));
+            let cards = self.room.clone();
+            let state = self.state_hash();
+            self.emit(GameEvent::RoomDealt { cards, dungeon_left: self.dungeon.len(), state });
+        }
+
+        let worst_case = self.room_worst_case();
+        if worst_case >= self.health {
+            self.message = format!(
+                "{}Deadly room! Its monsters can deal {} damage against your {} HP",
+                self.settings.theme.icon("⚠ "),
+                worst_case,
+                self.health
+            );
+            self.sticky_message = self.message.clone();
+            if self.settings.animations {
+                self.deadly_flash = DEADLY_FLASH_TICKS;
+            }
+        }
+    }
+
+    /// Damage from fighting every monster in the room, each with the weapon
+    /// when it can be used.
+    fn room_worst_case(&self) -> i32 {
+        self.room
+            .iter()
+            .filter(|c| c.is_monster())
+            .map(|c| self.best_damage(c))
+            .sum()
+    }
+
+    /// Least damage the rest of this room can cost, trying every order of
+    /// the plays left with and without the weapon.
+    fn room_optimal_damage(&self) -> i32 {
+        let plays = self.settings.plays_per_room().saturating_sub(self.cards_played_this_turn);
+        self.cheapest_line(&self.room, self.weapon.clone(), plays)
+    }
+
+    fn cheapest_line(&self, room: &[Card], weapon: Option<Weapon>, plays: u8) -> i32 {
+        if plays == 0 || room.is_empty() {
+            return 0;
+        }
+        let mut best = i32::MAX;
+        for (index, card) in room.iter().enumerate() {
+            let mut rest = room.to_vec();
+            rest.remove(index);
+            let mut lines = Vec::new();
+            if card.is_potion() {
+                lines.push((0, weapon.clone()));
+            } else if card.is_weapon() {
+                lines.push((0, Some(Weapon { card: *card, last_monster_slain: None })));
+            } else {
+                lines.push((card.value() as i32, weapon.clone()));
+                if let Some(w) = &weapon
+                    && w.can_use_against(card.value(), self.settings.durability)
+                {
+                    let damage = self.settings.combat.damage(card.value(), w.card.value());
+                    let dulled = Weapon { card: w.card, last_monster_slain: Some(card.value()) };
+                    lines.push((damage, Some(dulled)));
+                }
+            }
+            for (damage, weapon) in lines {
+                best = best.min(damage + self.cheapest_line(&rest, weapon, plays - 1));
+            }
+        }
+        best
+    }
+
+    /// The `--assist` read on a skip decision: this room's cost played well,
+    /// against a rough sense of what the dungeon holds.
+    fn skip_assist(&self) -> Option<String> {
+        if !self.settings.assist || !self.assist_mode || self.game_over || !self.can_skip() {
+            return None;
+        }
+        let monsters: Vec<i32> = self
+            .dungeon
+            .iter()
+            .filter(|c| c.is_monster())
+            .map(|c| c.value() as i32)
+            .collect();
+        let unknown = if monsters.is_empty() {
+            "no monsters left to draw".to_string()
+        } else {
+            let average = monsters.iter().sum::<i32>() as f32 / monsters.len() as f32;
+            format!("dungeon monsters average {:.1}", average)
+        };
+        Some(format!(
+            "This room ≈ {} dmg optimal; skipping risks unknown ({})",
+            self.room_optimal_damage(),
+            unknown
+        ))
+    }
+
+    /// The best final score still in reach, if every monster left were slain
+    /// for free: HP topped up by the remaining potions, plus the classic
+    /// bonus when full HP leaves a potion to finish on. An upper bound - it
+    /// ignores fights and how potions fall into turns.
+    fn max_possible_score(&self) -> i32 {
+        let potions: Vec<i32> = self
+            .dungeon
+            .iter()
+            .chain(self.room.iter())
+            .filter(|c| c.is_potion())
+            .map(|c| c.value() as i32)
+            .collect();
+        let health = (self.health + potions.iter().sum::<i32>()).min(self.max_health);
+        let bonus = match potions.iter().max() {
+            Some(&largest) if self.settings.classic_bonus && health == self.max_health => largest,
+            _ => 0,
+        };
+        health + bonus - self.skip_penalty
+    }
+
+    /// The `--assist` line on what's still at stake.
+    fn score_assist(&self) -> Option<String> {
+        if !self.settings.assist || !self.assist_mode || self.game_over {
+            return None;
+        }
+        Some(format!(
+            "Max possible: {} (if you stopped now: {})",
+            self.max_possible_score(),
+            self.calculate_score()
+        ))
+    }
+
+    /// The HP `potion` would restore if drunk now, or `None` if it'd be
+    /// wasted. Under `--potions best` that's at the current HP, since the
+    /// heal waits for the end of the turn.
+    fn potion_heal(&self, potion: &Card) -> Option<i32> {
+        if !self.settings.potions.counts(potion.value(), self.turn_potion) {
+            return None;
+        }
+        Some((potion.value() as i32).min(self.max_health - self.health))
+    }
+
+    fn potion_would_be_wasted(&self, potion: &Card) -> bool {
+        self.potion_heal(potion).is_none()
+    }
+
+    fn play_potion(&mut self, index: usize) {
+        let card = self.room.remove(index);
+        self.played_this_turn.push(card);
+        self.emit(GameEvent::CardPlayed { card, play: "potion" });
+
+        self.moves += 1;
+        if let Some(heal) = self.potion_heal(&card) {
+            let replaced = self.turn_potion.is_some();
+            self.turn_potion = Some(card.value());
+            self.last_card_was_potion = Some(card);
+            if self.settings.potions.heals_at_turn_end() {
+                // settle_potion heals once the turn is over
+                self.message = if replaced {
+                    format!("Drank {} - the bigger potion counts, it heals at the end of the turn", card.display())
+                } else {
+                    format!("Drank {} - it heals at the end of the turn", card.display())
+                };
+            } else {
+                self.health += heal;
+                self.room_healed += heal;
+                self.emit(GameEvent::Heal { amount: heal, health: self.health });
+                self.potions_drunk += 1;
+                self.message = format!("Used {} - healed {} HP!", card.display(), heal);
+                self.log(LogEvent::PotionDrunk { card, healed: heal, health: self.health });
+            }
+        } else {
+            self.avoidable_mistakes += 1;
+            self.message = format!("Second potion - {} wasted!", card.display());
+            self.log(LogEvent::PotionWasted(card));
+        }
+
+        self.discard.push(card);
+        self.cards_played_this_turn += 1;
+        self.check_turn_complete();
+    }
+
+    /// Under `--potions best`, heal by the turn's largest potion now that
+    /// the turn is over.
+    fn settle_potion(&mut self) {
+        if !self.settings.potions.heals_at_turn_end() {
+            return;
+        }
+        let Some(value) = self.turn_potion.take() else {
+            return;
+        };
+        let card = Card { suit: Suit::Hearts, rank: value };
+        let heal = (value as i32).min(self.max_health - self.health);
+        self.health += heal;
+        self.room_healed += heal;
+        self.emit(GameEvent::Heal { amount: heal, health: self.health });
+        self.potions_drunk += 1;
+        self.message = format!("{} healed {} HP!", card.display(), heal);
+        self.log(LogEvent::PotionDrunk { card, healed: heal, health: self.health });
+    }
+
+    fn play_weapon(&mut self, index: usize) {
+        let card = self.room.remove(index);
+        self.played_this_turn.push(card);
+        self.emit(GameEvent::CardPlayed { card, play: "weapon" });
+        self.moves += 1;
+
+        if self.settings.two_weapons && self.spare.is_none() && self.weapon.is_some() {
+            // The free slot takes the old weapon instead of the discard pile
+            self.spare = self.weapon.take();
+            self.monsters_on_spare = std::mem::take(&mut self.monsters_on_weapon);
+            if let Some(spare) = self.spare.as_ref().map(|w| w.card) {
+                self.log(LogEvent::WeaponSpared { new: card, spare });
+            }
+        } else if let Some(ref old_weapon) = self.weapon {
+            let old = old_weapon.card;
+            self.weapon_history.push((old_weapon.card, self.monsters_on_weapon.len()));
+            self.discard.push(old_weapon.card);
+            self.discard.append(&mut self.monsters_on_weapon);
+            self.log(LogEvent::WeaponReplaced { old, new: card });
+        } else {
+            self.log(LogEvent::WeaponEquipped(card));
+        }
+
+        self.weapon = Some(Weapon {
+            card,
+            last_monster_slain: None,
+        });
+        self.weapons_equipped += 1;
+        self.last_card_was_potion = None;
+        self.message = format!("Equipped {}!", card.display());
+
+        self.cards_played_this_turn += 1;
+        self.check_turn_complete();
+    }
+
+    fn can_use_spare_on(&self, card: &Card) -> bool {
+        self.spare
+            .as_ref()
+            .is_some_and(|spare| spare.can_use_against(card.value(), self.settings.durability))
+    }
+
+    /// Put the spare in hand and the held weapon in the spare slot, each
+    /// keeping its own pile of slain monsters.
+    fn swap_weapons(&mut self) {
+        std::mem::swap(&mut self.weapon, &mut self.spare);
+        std::mem::swap(&mut self.monsters_on_weapon, &mut self.monsters_on_spare);
+    }
+
+    /// The fight that takes the least damage from the monster at `index`:
+    /// the held weapon, the spare, or bare hands.
+    fn best_fight(&self, index: usize) -> Action {
+        let card = &self.room[index];
+        let damage = |weapon: &Option<Weapon>| {
+            weapon.as_ref().map_or(i32::MAX, |w| self.settings.combat.damage(card.value(), w.card.value()))
+        };
+        let spare_better = !self.can_use_weapon_on(card) || damage(&self.spare) < damage(&self.weapon);
+        if self.can_use_spare_on(card) && spare_better {
+            Action::FightWithSpare(index)
+        } else {
+            Action::Fight { index, use_weapon: self.can_use_weapon_on(card) }
+        }
+    }
+
+    fn can_use_weapon_on(&self, card: &Card) -> bool {
+        if let Some(ref weapon) = self.weapon {
+            weapon.can_use_against(card.value(), self.settings.durability)
+        } else {
+            false
+        }
+    }
+
+    /// The D popup past the rule itself: what each weapon held can still
+    /// hit and which of this room's monsters that rules out, or a worked
+    /// example while there's nothing to show it on.
+    fn dulling_help(&self) -> Vec<String> {
+        let durability = self.settings.durability;
+        let monsters: Vec<&Card> = self.room.iter().filter(|c| c.is_monster()).collect();
+        let mut paragraphs = Vec::new();
+        for (slot, weapon) in [("Your", &self.weapon), ("Your spare", &self.spare)] {
+            let Some(weapon) = weapon else {
+                continue;
+            };
+            let card = weapon.card.display();
+            // Ranks as the cards show them, so a kill of 11 reads as a J
+            let rank = |rank| Card { suit: Suit::Spades, rank }.rank_str();
+            let last = weapon.last_monster_slain.map(rank).unwrap_or_default();
+            let reach = match weapon.max_hittable(durability) {
+                None if durability == Durability::Off => format!("{} {} can hit any monster.", slot, card),
+                None => format!("{} {} is fresh: it can hit any monster until it slays one.", slot, card),
+                Some(max) if max < 2 => {
+                    format!("{} {} last slew a {}, so it can't hit any monster now.", slot, card, last)
+                }
+                Some(max) => {
+                    format!("{} {} last slew a {}, so it can hit monsters up to {}.", slot, card, last, rank(max))
+                }
+            };
+            let (hit, missed): (Vec<&Card>, Vec<&Card>) =
+                monsters.iter().partition(|m| weapon.can_use_against(m.value(), durability));
+            let list = |cards: &[&Card]| cards.iter().map(|c| c.display()).collect::<Vec<_>>().join(", ");
+            let room = match (hit.is_empty(), missed.is_empty()) {
+                (true, true) => String::new(),
+                (false, true) => format!(" Here it can hit every monster: {}.", list(&hit)),
+                (true, false) => format!(" Here every monster is beyond it: {}.", list(&missed)),
+                (false, false) => format!(" Here it can hit {}, but not {}.", list(&hit), list(&missed)),
+            };
+            paragraphs.push(format!("{}{}", reach, room));
+        }
+        if paragraphs.is_empty() {
+            paragraphs.push("You hold no weapon: every fight is barehanded until you equip a diamond.".to_string());
+        }
+        // Worked on cards so it reads in ASCII and numeric ranks too
+        let card = |suit, rank| Card { suit, rank }.display();
+        let (jack, clubs_jack) = (card(Suit::Spades, 11), card(Suit::Clubs, 11));
+        let example = match durability {
+            Durability::Strict => Some(format!("then hit a {}, but not a {}", card(Suit::Spades, 10), clubs_jack)),
+            Durability::Equal => Some(format!("then hit a {}, but not a {}", clubs_jack, card(Suit::Spades, 12))),
+            Durability::Off => None,
+        };
+        if let Some(example) = example
+            && self.weapon.as_ref().is_none_or(|w| w.last_monster_slain.is_none())
+        {
+            paragraphs.push(format!(
+                "For example, a {} that slays a {} can {}. Fighting barehanded never dulls it.",
+                card(Suit::Diamonds, 5),
+                jack,
+                example
+            ));
+        }
+        paragraphs
+    }
+
+    /// Threat tint for a monster: green if the weapon takes it to 0 damage,
+    /// red if the best fight is lethal or it's a big monster the weapon can't
+    /// hit, yellow for anything that hurts but is survivable.
+    fn threat_color(&self, card: &Card) -> Color {
+        let usable = self.can_use_weapon_on(card);
+        let damage = self.best_damage(card);
+        if damage == 0 {
+            Color::Green
+        } else if damage >= self.health || (!usable && card.value() >= 10) {
+            Color::Red
+        } else {
+            Color::Yellow
+        }
+    }
+
+    /// Least damage this monster can deal: with whichever weapon can hit it
+    /// hardest, else barehanded.
+    fn best_damage(&self, card: &Card) -> i32 {
+        let with = |weapon: &Option<Weapon>, usable: bool| match weapon {
+            Some(w) if usable => self.settings.combat.damage(card.value(), w.card.value()),
+            _ => card.value() as i32,
+        };
+        with(&self.weapon, self.can_use_weapon_on(card)).min(with(&self.spare, self.can_use_spare_on(card)))
+    }
+
+    fn can_skip(&self) -> bool {
+        self.skip_blocker().is_none()
+    }
+
+    /// Why the room can't be skipped right now, if it can't.
+    fn skip_blocker(&self) -> Option<&'static str> {
+        if self.just_skipped {
+            Some("Cannot skip two rooms in a row!")
+        } else if self.cards_played_this_turn > 0 {
+            Some("Cannot skip after playing cards!")
+        } else if self.dungeon.is_empty() {
+            // The cards would only be dealt straight back
+            Some("Nowhere left to flee - these are the last cards")
+        } else if self.skip_is_lethal() {
+            Some("Too weak to flee - skipping would cost your last HP")
+        } else {
+            None
+        }
+    }
+
+    /// A skip this late puts the room straight back in the player's path:
+    /// no more than two rooms' worth of cards are left to hide it under.
+    fn skip_is_endgame(&self) -> bool {
+        self.dungeon.len() <= ENDGAME_CARDS
+    }
+
+    /// Paying the HP toll for a skip would kill the player.
+    fn skip_is_lethal(&self) -> bool {
+        self.settings.skip_penalty == SkipPenalty::Health && self.health <= SKIP_PENALTY
+    }
+
+    /// True when every play left kills the player: only monsters remain, each
+    /// lethal even at its cheapest, and skipping isn't allowed.
+    fn no_safe_moves(&self) -> bool {
+        !self.game_over
+            && !self.room.is_empty()
+            && !self.can_skip()
+            && self
+                .room
+                .iter()
+                .all(|c| c.is_monster() && self.best_damage(c) >= self.health)
+    }
+
+    /// Every move `apply_action` would accept right now: each card in the
+    /// room with each way to play it, then a skip if one is allowed. Empty
+    /// once the run is over.
+    fn legal_moves(&self) -> Vec<Action> {
+        if self.game_over {
+            return Vec::new();
+        }
+        legal_moves_in(&self.room, &self.weapon, &self.spare, self.can_skip(), self.settings.durability)
+    }
+
+    /// A move is safe when it doesn't kill the player.
+    fn is_safe(&self, action: Action) -> bool {
+        match action {
+            Action::Fight { index, use_weapon } => {
+                let card = &self.room[index];
+                let damage = match (&self.weapon, use_weapon) {
+                    (Some(w), true) => self.settings.combat.damage(card.value(), w.card.value()),
+                    _ => card.value() as i32,
+                };
+                damage < self.health
+            }
+            Action::FightWithSpare(index) => {
+                let card = &self.room[index];
+                let damage = match &self.spare {
+                    Some(w) => self.settings.combat.damage(card.value(), w.card.value()),
+                    None => card.value() as i32,
+                };
+                damage < self.health
+            }
+            _ => true,
+        }
+    }
+
+    /// The move auto-advance may take: the only safe one, if there is exactly one.
+    fn forced_action(&self) -> Option<Action> {
+        if self.game_over {
+            return None;
+        }
+        let mut safe = self.legal_moves().into_iter().filter(|&a| self.is_safe(a));
+        match (safe.next(), safe.next()) {
+            (Some(action), None) => Some(action),
+            _ => None,
+        }
+    }
+
+    fn describe_action(&self, action: Action) -> String {
+        describe_move(&self.room, action)
+    }
+
+    /// First press names the forced move, a second press plays it.
+    fn auto_advance(&mut self) {
+        let Some(action) = self.forced_action() else {
+            self.auto_pending = None;
+            self.message = "No single safe move to auto-play - your call".to_string();
+            return;
+        };
+        if self.auto_pending == Some(action) {
+            self.auto_pending = None;
+            if let Err(err) = self.apply_action(action) {
+                self.message = err.to_string();
+            }
+        } else {
+            self.auto_pending = Some(action);
+            self.message = format!("Only safe move: {} - press A again", self.describe_action(action));
+        }
+    }
+
+    /// What playing this card would do right now.
+    fn card_outcome(&self, card: &Card) -> String {
+        if card.is_monster() {
+            self.monster_outcome(card).into_iter().map(|(text, _)| text).collect()
+        } else if card.is_weapon() {
+            match &self.weapon {
+                Some(held) => {
+                    let gain = card.value() as i32 - held.card.value() as i32;
+                    // Swapping only gives something up when the old weapon was dulled.
+                    let dulled = held.last_monster_slain.is_some()
+                        && self.settings.durability != Durability::Off;
+                    format!(
+                        "equip {} (was {}): {:+} power{}",
+                        card.display(),
+                        held.card.display(),
+                        gain,
+                        if dulled { " but resets dulling" } else { "" }
+                    )
+                }
+                None => format!("equip for {} attack power", card.value()),
+            }
+        } else if let Some(heal) = self.potion_heal(card) {
+            let when = if self.settings.potions.heals_at_turn_end() { " at turn end" } else { "" };
+            format!("heal {} HP{}", heal, when)
+        } else {
+            "wasted - already used potion".to_string()
+        }
+    }
+
+    /// A monster's `card_outcome` in pieces, each damage figure tagged with
+    /// its amount so the info line can color it.
+    fn monster_outcome(&self, card: &Card) -> Vec<(String, Option<i32>)> {
+        let damage = |amount: i32| (amount.to_string(), Some(amount));
+        let text = |text: &str| (text.to_string(), None);
+        let barehanded = card.value() as i32;
+        let mut parts = if self.can_use_weapon_on(card) {
+            let wpn = self.weapon.as_ref().unwrap();
+            let wpn_dmg = self.settings.combat.damage(card.value(), wpn.card.value());
+            vec![damage(barehanded), text(" dmg barehanded, "), damage(wpn_dmg), text(" with weapon")]
+        } else if self.weapon.is_some() {
+            vec![damage(barehanded), text(" damage (too strong for your weapon)")]
+        } else {
+            vec![damage(barehanded), text(" damage")]
+        };
+        if let Some(spare) = &self.spare {
+            if self.can_use_spare_on(card) {
+                let spare_dmg = self.settings.combat.damage(card.value(), spare.card.value());
+                parts.extend([text(", "), damage(spare_dmg), text(" with spare")]);
+            } else {
+                parts.push(text(", spare too dull"));
+            }
+        }
+        parts
+    }
+
+    /// With `--lethal-warning` at half HP or less, the lines to box in place
+    /// of the info line when the selected monster would kill barehanded,
+    /// and whether every fight with it is fatal, not just the bare one.
+    fn lethal_warning(&self) -> Option<(Vec<String>, bool)> {
+        let card = self.room.get(self.selected_index).filter(|c| c.is_monster())?;
+        let barehanded = card.value() as i32;
+        if !self.settings.lethal_warning || self.game_over || self.health * 2 > self.max_health
+            || barehanded < self.health
+        {
+            return None;
+        }
+        let best = self.best_damage(card);
+        let critical = best >= self.health;
+        let headline = format!(
+            "{}LETHAL: {} deals {} barehanded - you have {} HP",
+            self.settings.theme.icon("☠ "),
+            self.card_label(card),
+            barehanded,
+            self.health
+        );
+        let advice = if !critical {
+            format!("Fight it with your weapon: take {} and live on {} HP", best, self.health - best)
+        } else if self.can_skip() {
+            "Every fight with it kills you - skip the room or play another card".to_string()
+        } else {
+            "Every fight with it kills you - play another card first".to_string()
+        };
+        Some((vec![headline, advice], critical))
+    }
+
+    /// With one play left and two cards up, the card that stays behind if
+    /// the selected one is played.
+    fn carry_candidate(&self) -> Option<usize> {
+        let carrying = self.settings.carry;
+        (carrying && self.cards_played_this_turn == 2 && self.room.len() == 2 && self.selected_index < 2)
+            .then(|| 1 - self.selected_index)
+    }
+
+    /// The strongest weapon in the room, when hints are on and the player is unarmed.
+    fn equip_hint(&self) -> Option<usize> {
+        if !self.settings.equip_hints || !self.assist_mode || self.weapon.is_some() {
+            return None;
+        }
+        (0..self.room.len())
+            .filter(|&i| self.room[i].is_weapon())
+            .max_by_key(|&i| self.room[i].value())
+    }
+
+    fn fight_monster(&mut self, index: usize, use_weapon: bool) {
+        let card = self.room.remove(index);
+        self.played_this_turn.push(card);
+        let play = if use_weapon { "weapon_fight" } else { "barehanded" };
+        self.emit(GameEvent::CardPlayed { card, play });
+        self.moves += 1;
+        if !use_weapon && self.can_use_weapon_on(&card) {
+            self.avoidable_mistakes += 1;
+        }
+
+        let damage = if use_weapon {
+            let weapon = self.weapon.as_mut().unwrap();
+            let dmg = self.settings.combat.damage(card.value(), weapon.card.value());
+            weapon.last_monster_slain = Some(card.value());
+            self.kills_with_weapon += 1;
+            let weapon = weapon.card;
+            self.monsters_on_weapon.push(card);
+            self.damage_prevented += card.value() as i32 - dmg;
+            self.message = format!("Slew {} with weapon - took {} damage!", card.display(), dmg);
+            let health = self.health - dmg;
+            self.log(LogEvent::KilledWithWeapon { monster: card, weapon, damage: dmg, health });
+            dmg
+        } else {
+            let dmg = card.value() as i32;
+            self.discard.push(card);
+            self.kills_barehanded += 1;
+            self.message = format!("Fought {} barehanded - took {} damage!", card.display(), dmg);
+            let health = self.health - dmg;
+            self.log(LogEvent::FoughtBarehanded { monster: card, damage: dmg, health });
+            dmg
+        };
+
+        self.health -= damage;
+        self.room_damage += damage;
+        self.damage_by_suit[(card.suit == Suit::Clubs) as usize] += damage;
+        self.emit(GameEvent::Damage { amount: damage, health: self.health.max(0) });
+        if self.settings.effects && damage > 0 {
+            self.damage_popup = Some(DamagePopup {
+                amount: damage,
+                area: self.card_areas.get(index).copied().unwrap_or_default(),
+                ticks_left: POPUP_TICKS,
+            });
+        }
+        self.last_card_was_potion = None;
+        self.cards_played_this_turn += 1;
+
+        if self.health <= 0 {
+            self.health = 0;
+            self.game_over = true;
+            self.won = false;
+            self.killing_blow = Some((card, use_weapon, damage));
+            self.log(LogEvent::Died);
+            self.close_room(false);
+            let score = self.calculate_score();
+            let state = self.state_hash();
+            self.emit(GameEvent::GameOver { won: false, score, state });
+            self.screen = Screen::GameOver;
+            self.save_replay();
+        } else {
+            self.check_turn_complete();
+        }
+    }
+
+    fn check_turn_complete(&mut self) {
+        // A short last room can empty before three plays
+        let cleared = self.dungeon.is_empty() && self.room.is_empty();
+        if self.cards_played_this_turn >= self.settings.plays_per_room() || cleared {
+            self.settle_potion();
+            self.turn_number += 1;
+            self.hp_by_turn.push(self.health);
+            // Monsters faced and not a scratch taken
+            self.flawless_room = self.room_damage == 0 && self.played_this_turn.iter().any(|c| c.is_monster());
+            self.close_room(false);
+            let room_over = !(self.dungeon.is_empty() && self.room.is_empty());
+            if self.settings.turn_summary && room_over {
+                self.screen = Screen::TurnSummary;
+                self.summary_ticks = SUMMARY_TICKS;
+            }
+
+            if self.dungeon.is_empty() && self.room.len() == 1 {
+                // Must play final card
+                self.message = "Final card! You must face it.".to_string();
+                self.sticky_message = self.message.clone();
+                self.cards_played_this_turn = 0;
+                self.turn_potion = None;
+                self.selected_index = 0;
+            } else if self.dungeon.is_empty() && self.room.is_empty() {
+                self.game_over = true;
+                self.won = true;
+                let score = self.calculate_score();
+                self.log(LogEvent::Victory { score });
+                let state = self.state_hash();
+                self.emit(GameEvent::GameOver { won: true, score, state });
+                self.screen = Screen::Results;
+                self.save_replay();
+            } else {
+                self.just_skipped = false;
+                // Say the cards changed, after what the last play did
+                let news = if self.flawless_room { "Flawless room! New room!" } else { "New room!" };
+                self.message = if self.message.is_empty() {
+                    news.to_string()
+                } else {
+                    format!("{} - {}", self.message, news)
+                };
+                if self.settings.animations {
+                    self.new_room_flash = NEW_ROOM_FLASH_TICKS;
+                }
+                self.deal_room();
+                self.save_checkpoint();
+            }
+        }
+
+        if !self.room.is_empty() {
+            self.selected_index = self.sensible_selection();
+        }
+    }
+
+    /// Record the room that just ended and start counting afresh.
+    fn close_room(&mut self, skipped: bool) {
+        let slain = self.played_this_turn.iter().filter(|c| c.is_monster()).count();
+        self.rooms.push(RoomSummary {
+            skipped,
+            damage: self.room_damage,
+            healed: self.room_healed,
+            slain,
+        });
+        self.room_damage = 0;
+        self.room_healed = 0;
+    }
+
+    fn skip_room(&mut self) {
+        let cards = self.room.clone();
+        self.emit(GameEvent::RoomSkipped { cards: cards.clone() });
+        match self.settings.skip_order {
+            SkipOrder::Bottom => self.dungeon.append(&mut self.room),
+            SkipOrder::Shuffle => {
+                // Seeded from the run and the room count, so a replayed
+                // checkpoint shuffles the same way
+                let mut rng = seeded_rng(self.seed.wrapping_add(self.rooms.len() as u64));
+                for card in self.room.drain(..) {
+                    let at = below(&mut rng, self.dungeon.len() + 1);
+                    self.dungeon.insert(at, card);
+                }
+            }
+            SkipOrder::Front => {
+                let at = self.dungeon.len().min(4);
+                self.dungeon.splice(at..at, self.room.drain(..));
+            }
+        }
+        self.just_skipped = true;
+        self.close_room(true);
+        self.log(LogEvent::RoomSkipped(cards));
+        self.message = "Skipped room".to_string();
+        match self.settings.skip_penalty {
+            SkipPenalty::Off => {}
+            SkipPenalty::Health => {
+                self.health -= SKIP_PENALTY;
+                self.emit(GameEvent::Damage { amount: SKIP_PENALTY, health: self.health });
+                self.log(LogEvent::SkipPaidHealth(SKIP_PENALTY));
+                self.message = format!("Skipped room (-{} HP)", SKIP_PENALTY);
+            }
+            SkipPenalty::Score => {
+                self.skip_penalty += SKIP_PENALTY;
+                self.log(LogEvent::SkipCostsScore(SKIP_PENALTY));
+                self.message = format!("Skipped room (-{} score)", SKIP_PENALTY);
+            }
+        }
+        self.deal_room();
+        self.save_checkpoint();
+    }
+
+    /// Lifetime-in-run tallies, one line each, for the stats modal and game over.
+    fn tally_lines(&self) -> Vec<String> {
+        vec![
+            format!(
+                "Monsters defeated: {} ({} with weapon, {} barehanded)",
+                self.kills_with_weapon + self.kills_barehanded,
+                self.kills_with_weapon,
+                self.kills_barehanded
+            ),
+            format!(
+                "Damage taken: {} dealt {}, {} dealt {}",
+                Suit::Spades.symbol(),
+                self.damage_by_suit[0],
+                Suit::Clubs.symbol(),
+                self.damage_by_suit[1]
+            ),
+            format!("Potions drunk: {}", self.potions_drunk),
+            format!("Weapons equipped: {}", self.weapons_equipped),
+        ]
+    }
+
+    /// How much of the monster damage faced the weapons soaked, e.g.
+    /// "Weapon efficiency: 64% (prevented 45, took 25)". `None` before any
+    /// monster has been fought.
+    fn weapon_efficiency_line(&self) -> Option<String> {
+        let taken: i32 = self.damage_by_suit.iter().sum();
+        let faced = self.damage_prevented + taken;
+        (faced > 0).then(|| {
+            format!(
+                "Weapon efficiency: {}% (prevented {}, took {})",
+                self.damage_prevented * 100 / faced,
+                self.damage_prevented,
+                taken
+            )
+        })
+    }
+
+    /// Advance timed effects; called every TICK_RATE by run_app.
+    fn on_tick(&mut self) {
+        // A paused game stands still: no clock, flashes, popups or recaps
+        if self.screen == Screen::Paused {
+            return;
+        }
+        self.ticks += 1;
+        if !self.game_over && self.screen != Screen::Resume {
+            self.play_ticks += 1;
+        }
+        self.deadly_flash = self.deadly_flash.saturating_sub(1);
+        self.new_room_flash = self.new_room_flash.saturating_sub(1);
+        // The deal plays out on the board, not behind a recap or menu
+        if let Some((first, ticks)) = self.dealing.as_mut()
+            && self.screen == Screen::Game
+        {
+            *ticks += 1;
+            if *ticks as usize > (self.room.len().saturating_sub(*first)) * DEAL_STEP_TICKS as usize {
+                self.dealing = None;
+            }
+        }
+        if self.screen == Screen::TurnSummary {
+            self.summary_ticks = self.summary_ticks.saturating_sub(1);
+            if self.summary_ticks == 0 {
+                self.screen = Screen::Game;
+            }
+        }
+        if let Some(popup) = self.damage_popup.as_mut() {
+            popup.ticks_left = popup.ticks_left.saturating_sub(1);
+            if popup.ticks_left == 0 {
+                self.damage_popup = None;
+            }
+        }
+        self.expire_message();
+        if let Some((forward, since)) = self.held
+            && self.screen == Screen::Game
+        {
+            let held_for = self.ticks - since;
+            if held_for >= HOLD_FAST_TICKS || (held_for >= HOLD_DELAY_TICKS && held_for % 2 == 1) {
+                self.move_selection(forward);
+            }
+        }
+    }
+
+    /// Whether room slot `index` is on the board yet, and whether it has only
+    /// just arrived (drawn dimmed, fading in).
+    fn deal_state(&self, index: usize) -> (bool, bool) {
+        match self.dealing {
+            Some((first, ticks)) if index >= first => {
+                let due = (index - first) as u8 * DEAL_STEP_TICKS;
+                (ticks >= due, ticks < due + DEAL_STEP_TICKS)
+            }
+            _ => (true, false),
+        }
+    }
+
+    /// With `--message-timeout`, blank the message line once it has sat
+    /// unchanged with no key pressed for that long. Warnings about the
+    /// room at hand stay up until something replaces them.
+    fn expire_message(&mut self) {
+        if self.message != self.message_shown {
+            self.message_shown = self.message.clone();
+            self.message_since = self.ticks;
+        }
+        let timeout = self.settings.message_timeout as u64 * 1000 / TICK_RATE.as_millis() as u64;
+        if timeout == 0 || self.message.is_empty() || self.message == self.sticky_message {
+            return;
+        }
+        if self.ticks - self.message_since >= timeout {
+            self.message.clear();
+        }
+    }
+
+    /// This run's HP by turn as a sparkline over the average of past runs,
+    /// both scaled to max HP. Nothing until there's a past run to compare.
+    fn hp_chart_lines(&self) -> Vec<Line<'static>> {
+        let Some((average, runs)) = &self.average_hp else {
+            return Vec::new();
+        };
+        let mut hp: Vec<f32> = self.hp_by_turn.iter().map(|&h| h as f32).collect();
+        if !self.won {
+            hp.push(self.health as f32);
+        }
+        let scale = self.max_health.max(1) as f32;
+        let sparks = Glyphs::get().sparks;
+        let spark = |values: &[f32]| -> String {
+            values
+                .iter()
+                .map(|&v| sparks[((v / scale * 7.0).round().clamp(0.0, 7.0)) as usize])
+                .collect()
+        };
+        let width = hp.len().max(average.len());
+        let label = |text: &str| Span::styled(format!("{:<9}", text), Style::default().fg(Color::DarkGray));
+        vec![
+            Line::from(vec![
+                label("This run"),
+                Span::styled(format!("{:<width$}", spark(&hp)), Style::default().fg(Color::Green)),
+            ]),
+            Line::from(vec![
+                label("Average"),
+                Span::styled(format!("{:<width$}", spark(average)), Style::default().fg(Color::Yellow)),
+            ]),
+            Line::from(Span::styled(
+                format!("HP at the start of each turn, against your last run on {} seeds", runs),
+                Style::default().fg(Color::DarkGray),
+            )),
+        ]
+    }
+
+    /// The keys under the game-over screen; an ironman run only gets to
+    /// leave. While a leaderboard name is being typed, that comes first.
+    fn game_over_prompt(&self) -> String {
+        if self.leaderboard_name.is_some() {
+            "Type a name · Enter adds the run to the leaderboard · Esc leaves it off".to_string()
+        } else if self.settings.ironman {
+            "Ironman run recorded · Log [L] · any other key exits".to_string()
+        } else {
+            "Play again? [Y/n] · Retry this seed [R] · Log [L]".to_string()
+        }
+    }
+
+    /// With `--leaderboard`, the best runs under these rules, this one
+    /// picked out once it's added, and the name being typed for it.
+    fn leaderboard_lines(&self) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        for (i, entry) in self.leaderboard.iter().enumerate() {
+            let text = format!(
+                "{}. {:<width$} {:>4} {}",
+                i + 1,
+                entry.name,
+                entry.score,
+                if entry.won { "won " } else { "died" },
+                width = LEADERBOARD_NAME_LEN
+            );
+            let style = if self.leaderboard_place == Some(i + 1) {
+                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            lines.push(Line::from(Span::styled(text, style)));
+        }
+        if self.leaderboard.is_empty() {
+            lines.push(Line::from("No runs under these rules yet"));
+        }
+        if let Some(place) = self.leaderboard_place.filter(|&place| place > LEADERBOARD_SHOWN) {
+            lines.push(Line::from(format!("This run placed {}", place)));
+        }
+        if let Some(reason) = self.unranked_reason() {
+            lines.push(Line::from(Span::styled(reason, Style::default().fg(Color::DarkGray))));
+        }
+        if let Some(ref name) = self.leaderboard_name {
+            lines.push(Line::from(Span::styled(
+                format!("Your name: {}_", name),
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            )));
+        }
+        lines
+    }
+
+    /// "K♠", or "K♠ Death Knight" with flavor names on.
+    fn card_label(&self, card: &Card) -> String {
+        match card.monster_name().filter(|_| self.settings.flavor) {
+            Some(name) => format!("{} {}", card.display(), name),
+            None => card.display(),
+        }
+    }
+
+    /// "Seed: 42", noting a run that took a mulligan to get here.
+    fn seed_line(&self) -> String {
+        if self.mulligan_used {
+            format!("Seed: {} (after a mulligan)", self.seed)
+        } else {
+            format!("Seed: {}", self.seed)
+        }
+    }
+
+    /// A fingerprint of the position: HP, weapons and their dulling, the
+    /// dungeon and room in order, the played cards as a set, and where the
+    /// turn stands. Two games in the same position hash alike however they
+    /// got there. FNV-1a rather than std's hasher, whose output may change
+    /// between Rust releases.
+    fn state_hash(&self) -> u64 {
+        let code = |c: &Card| c.index() as u8;
+        let weapon = |w: &Option<Weapon>| match w {
+            Some(w) => [w.card.rank, w.last_monster_slain.unwrap_or(0)],
+            None => [0, 0],
+        };
+        let sorted = |cards: &[Card]| {
+            let mut codes: Vec<u8> = cards.iter().map(code).collect();
+            codes.sort();
+            codes
+        };
+        let mut bytes = Vec::new();
+        bytes.extend(self.health.to_le_bytes());
+        bytes.extend(self.max_health.to_le_bytes());
+        bytes.extend(weapon(&self.weapon));
+        bytes.extend(weapon(&self.spare));
+        // 0xFF can't be a card, so it marks where each list ends
+        for cards in [
+            self.dungeon.iter().map(code).collect(),
+            self.room.iter().map(code).collect(),
+            sorted(&self.discard),
+            sorted(&self.monsters_on_weapon),
+            sorted(&self.monsters_on_spare),
+        ] {
+            bytes.extend(cards);
+            bytes.push(0xFF);
+        }
+        bytes.extend([
+            self.cards_played_this_turn,
+            self.turn_potion.unwrap_or(0),
+            self.just_skipped as u8,
+            self.game_over as u8,
+            self.won as u8,
+        ]);
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+    }
+
+    /// Time spent on the run so far, e.g. "3:07".
+    fn clock(&self) -> String {
+        let secs = self.play_ticks * TICK_RATE.as_millis() as u64 / 1000;
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+
+    /// With `--autosave`, snapshot the run as the new room begins.
+    fn save_checkpoint(&mut self) {
+        if !self.persist || !self.settings.autosave || self.game_over {
+            return;
+        }
+        let checkpoint = Checkpoint {
+            seed: self.seed,
+            rules: Checkpoint::rules_of(&self.settings),
+            deck: self.initial_order.clone(),
+            moves: self.history.clone(),
+            turn: self.turn_number,
+            summary: Some(SaveSummary::of(self)),
+        };
+        if let Err(err) = checkpoint.save() {
+            self.log(LogEvent::Warning(err.to_string()));
+            self.message = err.to_string();
+        }
+    }
+
+    /// Replay a checkpoint's moves on its deck to pick the run back up.
+    fn resume(settings: Settings, checkpoint: &Checkpoint) -> Result<Self, String> {
+        let puzzle = settings.puzzle.clone();
+        let mut replay_settings = settings;
+        replay_settings.puzzle = Some(checkpoint.deck.clone());
+        let mut game = GameState::with_seed(replay_settings, checkpoint.seed);
+        game.persist = false;
+        for &action in &checkpoint.moves {
+            game.apply_action(action)
+                .map_err(|err| format!("Checkpoint doesn't replay ({})", err))?;
+        }
+        if game.game_over {
+            return Err("Checkpoint is of a finished run".to_string());
+        }
+        game.settings.puzzle = puzzle;
+        game.persist = !game.settings.debug;
+        game.screen = Screen::Game;
+        game.damage_popup = None;
+        game.new_room_flash = 0;
+        game.dealing = None;
+        game.message = format!("Resumed on turn {}", game.turn_number);
+        Ok(game)
+    }
+
+    /// Answer the Resume screen: pick the checkpointed run back up, or
+    /// throw it away and keep the fresh deal. Until then `log_stream` holds
+    /// the `--log-json` stream with nothing written, so only the run that's
+    /// kept gets replayed into it.
+    fn answer_resume(&mut self, resume: bool) {
+        self.screen = Screen::Game;
+        let Some(checkpoint) = self.pending_resume.take() else {
+            return;
+        };
+        if resume {
+            match GameState::resume(self.settings.clone(), &checkpoint) {
+                Ok(game) => {
+                    let events = self.events.take();
+                    let log_stream = self.log_stream.take();
+                    *self = game;
+                    self.attach_events(events);
+                    self.attach_log(log_stream);
+                    return;
+                }
+                Err(err) => self.message = err,
+            }
+        }
+        let log_stream = self.log_stream.take();
+        self.attach_log(log_stream);
+        if let Err(err) = Checkpoint::clear() {
+            self.message = err.to_string();
+        }
+    }
+
+    fn save_replay(&mut self) {
+        if !self.persist {
+            return;
+        }
+        if self.settings.autosave
+            && let Err(err) = Checkpoint::clear()
+        {
+            self.log(LogEvent::Warning(err.to_string()));
+        }
+        // Read before this run is saved, so it isn't compared with itself
+        match Replay::average_hp() {
+            Ok(average) => self.average_hp = average,
+            Err(err) => self.log(LogEvent::Warning(err.to_string())),
+        }
+        let replay = Replay {
+            seed: self.seed,
+            hp_by_turn: self.hp_by_turn.clone(),
+        };
+        if let Err(err) = replay.save() {
+            self.log(LogEvent::Warning(err.to_string()));
+            self.message = err.to_string();
+        }
+        let saved = match storage::runs_csv_path() {
+            Some(path) => storage::append_csv_row(&path, RUNS_CSV_HEADER, &self.csv_row(), "record run"),
+            None => Err(StorageError::no_home("record run")),
+        };
+        if let Err(err) = saved {
+            self.log(LogEvent::Warning(err.to_string()));
+            self.message = err.to_string();
+        }
+        if self.settings.leaderboard.is_some() {
+            self.load_leaderboard();
+            if self.unranked_reason().is_none() {
+                self.leaderboard_name = Some(String::new());
+            }
+        }
+    }
+
+    /// Add this run under the name typed. Enter on an empty name does
+    /// nothing, so a held key can't throw the entry away.
+    fn submit_leaderboard(&mut self) {
+        let (Some(name), Some(path)) = (self.leaderboard_name.as_deref(), self.settings.leaderboard.as_deref())
+        else {
+            return;
+        };
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return;
+        }
+        let entry = LeaderboardEntry {
+            name,
+            score: self.calculate_score(),
+            won: self.won,
+            seed: self.seed,
+            rules: Checkpoint::rules_of(&self.settings),
+        };
+        if let Err(err) = entry.save(Path::new(path)) {
+            self.log(LogEvent::Warning(err.to_string()));
+            self.message = err.to_string();
+            return;
+        }
+        self.leaderboard_name = None;
+        // Ties rank the earlier run first, so ours comes after every
+        // equal score (including any added since by another instance)
+        let entries = self.load_leaderboard();
+        let place = entries.iter().filter(|e| e.score >= entry.score).count();
+        self.leaderboard_place = Some(place);
+        self.message = format!("{} placed {} of {} on the leaderboard", entry.name, place, entries.len());
+    }
+
+    /// Why this run can't go on the leaderboard, if it can't. Ironman runs
+    /// can, and are ranked apart by their rules.
+    fn unranked_reason(&self) -> Option<&'static str> {
+        if self.settings.debug {
+            Some("debug runs aren't ranked")
+        } else if self.settings.safe {
+            Some("safe mode runs aren't ranked")
+        } else if self.mulligan_used {
+            Some("runs that took a mulligan aren't ranked")
+        } else {
+            None
+        }
+    }
+
+    /// Read the top of the leaderboard for the game-over screen. Returns
+    /// every run under these rules, for placing a new one.
+    fn load_leaderboard(&mut self) -> Vec<LeaderboardEntry> {
+        let Some(ref path) = self.settings.leaderboard else {
+            return Vec::new();
+        };
+        match LeaderboardEntry::load(Path::new(path), &Checkpoint::rules_of(&self.settings)) {
+            Ok(entries) => {
+                self.leaderboard = entries.iter().take(LEADERBOARD_SHOWN).cloned().collect();
+                entries
+            }
+            Err(err) => {
+                self.log(LogEvent::Warning(err.to_string()));
+                self.message = err.to_string();
+                Vec::new()
+            }
+        }
+    }
+
+    /// This run as a line of `runs.csv`, in `RUNS_CSV_HEADER` order.
+    fn csv_row(&self) -> String {
+        let finished = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let (killed_by, killing_damage) = match self.killing_blow {
+            Some((card, _, damage)) => (card.display(), damage.to_string()),
+            None => (String::new(), String::new()),
+        };
+        let fields = [
+            finished.to_string(),
+            self.seed.to_string(),
+            Checkpoint::rules_of(&self.settings),
+            if self.won { "won" } else { "died" }.to_string(),
+            self.calculate_score().to_string(),
+            self.turn_number.to_string(),
+            killed_by,
+            killing_damage,
+            self.damage_by_suit[0].to_string(),
+            self.damage_by_suit[1].to_string(),
+            self.kills_with_weapon.to_string(),
+            self.kills_barehanded.to_string(),
+            self.potions_drunk.to_string(),
+            if self.mulligan_used { "yes" } else { "no" }.to_string(),
+        ];
+        fields.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(",")
+    }
+
+    /// HP lead over the ghost at the start of the current turn, if it got this far.
+    fn ghost_delta(&self) -> Option<i32> {
+        let ghost = self.ghost.as_ref()?;
+        let turn = self.hp_by_turn.len() - 1;
+        Some(self.hp_by_turn[turn] - ghost.hp_by_turn.get(turn)?)
+    }
+
+    /// A win scores the remaining HP. Under the classic bonus, a win at exactly
+    /// max HP whose last played card was a potion also adds that potion's value
+    /// (the heal it couldn't give). `last_card_was_potion` is cleared by any
+    /// weapon or monster play and by each new room, so it only survives if the
+    /// potion really was the final card. A loss scores HP minus the monsters
+    /// left in the dungeon and room.
+    fn calculate_score(&self) -> i32 {
+        if self.won {
+            let mut score = self.health;
+            if self.settings.classic_bonus
+                && self.health == self.max_health
+                && let Some(ref potion) = self.last_card_was_potion
+            {
+                score += potion.value() as i32;
+            }
+            score - self.skip_penalty
+        } else {
+            let remaining: i32 = self
+                .dungeon
+                .iter()
+                .chain(self.room.iter())
+                .filter(|c| c.is_monster())
+                .map(|c| c.value() as i32)
+                .sum();
+            self.health - remaining - self.skip_penalty
+        }
+    }
+
+    /// Play a move, checking its card is still in the room and is the kind
+    /// the action expects. Every input path goes through here so a stale or
+    /// malformed action (from a replay, event stream or other front-end)
+    /// can't reach `room.remove` or drink a monster.
+    fn apply_action(&mut self, action: Action) -> Result<(), MoveError> {
+        let target = match action {
+            Action::PlayPotion(index) => Some((index, "a potion")),
+            Action::PlayWeapon(index) => Some((index, "a weapon")),
+            Action::Fight { index, .. } | Action::FightWithSpare(index) => Some((index, "a monster")),
+            Action::Skip => None,
+        };
+        if let Some((index, expected)) = target {
+            let card = *self
+                .room
+                .get(index)
+                .ok_or(MoveError::IndexOutOfRange { index, len: self.room.len() })?;
+            let fits = match action {
+                Action::PlayPotion(_) => card.is_potion(),
+                Action::PlayWeapon(_) => card.is_weapon(),
+                _ => card.is_monster(),
+            };
+            if !fits {
+                return Err(MoveError::WrongCardType { card, expected });
+            }
+        }
+        if self.game_over {
+            return Err(MoveError::GameOver);
+        }
+        if action == Action::Skip
+            && let Some(reason) = self.skip_blocker()
+        {
+            return Err(MoveError::CannotSkip(reason));
+        }
+        if !self.legal_moves().contains(&action)
+            && let Some(&card) = target.and_then(|(index, _)| self.room.get(index))
+        {
+            return Err(MoveError::WeaponCantHit { card });
+        }
+        self.lethal_pending = None;
+        self.mulligan_available = false;
+        self.history.push(action);
+        match action {
+            Action::PlayPotion(index) => self.play_potion(index),
+            Action::PlayWeapon(index) => self.play_weapon(index),
+            Action::Fight { index, use_weapon } => self.fight_monster(index, use_weapon),
+            Action::FightWithSpare(index) => {
+                self.swap_weapons();
+                self.fight_monster(index, true);
+            }
+            Action::Skip => self.skip_room(),
+        }
+        Ok(())
+    }
+
+    /// Under `--safe`, hold back a fight that would end the run until it's
+    /// chosen a second time in a row. True when the fight was held.
+    fn hold_lethal(&mut self, action: Action) -> bool {
+        if !self.settings.safe || self.is_safe(action) || self.lethal_pending == Some(action) {
+            return false;
+        }
+        self.lethal_pending = Some(action);
+        self.message = format!(
+            "{}To {} would kill you - choose it again to confirm",
+            self.settings.theme.icon("☠ "),
+            self.describe_action(action)
+        );
+        true
+    }
+
+    /// Under `--safe`, take back the last card played in this room by
+    /// replaying the run without it.
+    fn undo(&mut self) {
+        if !self.settings.safe {
+            self.message = "Undo is only available with --safe".to_string();
+            return;
+        }
+        if self.game_over || self.cards_played_this_turn == 0 {
+            self.message = "Nothing to undo in this room".to_string();
+            return;
+        }
+        let mut moves = self.history.clone();
+        let Some(undone) = moves.pop() else {
+            return;
+        };
+        let checkpoint = Checkpoint {
+            seed: self.seed,
+            rules: String::new(),
+            deck: self.initial_order.clone(),
+            moves,
+            turn: self.turn_number,
+            summary: None,
+        };
+        match GameState::resume(self.settings.clone(), &checkpoint) {
+            Ok(mut game) => {
+                game.events = self.events.take();
+                game.log_stream = self.log_stream.take();
+                game.assist_mode = self.assist_mode;
+                game.show_analysis = self.show_analysis;
+                // The replay rebuilds the board, not the run around it
+                game.ticks = self.ticks;
+                game.play_ticks = self.play_ticks;
+                game.mulligan_used = self.mulligan_used;
+                game.log = std::mem::take(&mut self.log);
+                game.log_dropped = self.log_dropped;
+                let description = game.describe_action(undone);
+                game.message = format!("Took back: {}", description);
+                game.log(LogEvent::Undone(description));
+                *self = game;
+            }
+            Err(err) => self.message = err,
+        }
+    }
+
+    /// Run a `--debug` prompt command: `hp N`, `card CODE` (a card still
+    /// in the dungeon, into the selected slot or a free one), `deck`, `win`
+    /// or `lose`.
+    fn run_debug_command(&mut self, command: &str) {
+        let mut words = command.split_whitespace();
+        let result = match (words.next(), words.next()) {
+            (Some("hp"), Some(n)) => match n.parse::<i32>() {
+                Ok(hp) => {
+                    self.health = hp.clamp(1, self.max_health);
+                    Ok(format!("HP set to {}", self.health))
+                }
+                Err(_) => Err(format!("Not a number: {}", n)),
+            },
+            (Some("card"), Some(code)) => match Card::parse(code) {
+                Some(card) => {
+                    if self.room.contains(&card) {
+                        Err(format!("{} is already in the room", card.display()))
+                    } else if !self.dungeon.contains(&card) {
+                        // Played, slain, discarded or not in this deck at all
+                        Err(format!("{} isn't left in the dungeon", card.display()))
+                    } else {
+                        // Take it out of the deck so it isn't in the run twice
+                        self.dungeon.retain(|c| *c != card);
+                        if self.room.len() < 4 {
+                            self.room.push(card);
+                            Ok(format!("Dealt {}", card.display()))
+                        } else {
+                            let index = self.selected_index.min(self.room.len() - 1);
+                            let old = std::mem::replace(&mut self.room[index], card);
+                            self.dungeon.insert(0, old);
+                            Ok(format!("Swapped {} for {}", old.display(), card.display()))
+                        }
+                    }
+                }
+                None => Err(format!("Not a card: {} (e.g. SQ, D8, H10)", code)),
+            },
+            (Some("state"), None) => Ok(format!("State hash {:016x}", self.state_hash())),
+            (Some("deck"), None) => {
+                self.screen = Screen::DebugDeck;
+                Ok("Showing the dungeon order".to_string())
+            }
+            (Some(ending @ ("win" | "lose")), None) => {
+                let won = ending == "win";
+                if won {
+                    self.dungeon.clear();
+                    self.room.clear();
+                } else {
+                    self.health = 0;
+                }
+                self.game_over = true;
+                self.won = won;
+                let score = self.calculate_score();
+                self.log(if won { LogEvent::Victory { score } } else { LogEvent::Died });
+                self.screen = if won { Screen::Results } else { Screen::GameOver };
+                Ok(format!("Forced a {}", if won { "win" } else { "loss" }))
+            }
+            _ => Err("Commands: hp N · card CODE · deck · state · win · lose".to_string()),
+        };
+        match result {
+            Ok(done) => {
+                self.log(LogEvent::Debug(command.to_string()));
+                self.message = done;
+            }
+            Err(err) => self.message = err,
+        }
+    }
+
+    /// Play the card a player picked by Enter, number key or click. A monster
+    /// opens the combat choice instead while a weapon is held, or always
+    /// under `--confirm-fights`.
+    fn play_selected(&mut self, index: usize) -> Result<(), MoveError> {
+        let card = *self
+            .room
+            .get(index)
+            .ok_or(MoveError::IndexOutOfRange { index, len: self.room.len() })?;
+        self.selected_index = index;
+        if card.is_potion() {
+            self.apply_action(Action::PlayPotion(index))
+        } else if card.is_weapon() {
+            self.apply_action(Action::PlayWeapon(index))
+        } else if self.weapon.is_none() && !self.settings.confirm_fights {
+            self.apply_action(Action::Fight { index, use_weapon: false })
+        } else {
+            self.combat_card_index = Some(index);
+            self.combat_selection = 0;
+            self.lethal_pending = None;
+            self.screen = Screen::Combat;
+            Ok(())
+        }
+    }
+
+    /// Step the selection one card right or left, wrapping around the room
+    /// unless `wrap_navigation` is off.
+    fn move_selection(&mut self, forward: bool) {
+        let len = self.room.len();
+        if len == 0 {
+            return;
+        }
+        let last = len - 1;
+        self.selected_index = match (forward, self.settings.wrap_navigation) {
+            (true, true) => (self.selected_index + 1) % len,
+            (true, false) => (self.selected_index + 1).min(last),
+            (false, true) if self.selected_index == 0 => last,
+            (false, _) => self.selected_index.saturating_sub(1),
+        };
+    }
+
+    /// Move the selection to the next card after it that `wanted` picks,
+    /// wrapping around; the current card only counts if nothing else does.
+    /// With no such card, say `none` instead.
+    fn select_next(&mut self, wanted: impl Fn(&GameState, &Card) -> bool, none: &str) {
+        let len = self.room.len();
+        let next = (1..=len)
+            .map(|step| (self.selected_index + step) % len)
+            .find(|&index| wanted(self, &self.room[index]));
+        match next {
+            Some(index) => self.selected_index = index,
+            None => self.message = none.to_string(),
+        }
+    }
+
+    /// Where the selection should land after a play, so Enter can keep
+    /// going: the first card from where the played one was (wrapping) that
+    /// does some good, i.e. a better weapon, a potion that heals or a
+    /// monster that won't kill; failing that, anything that won't kill.
+    fn sensible_selection(&self) -> usize {
+        let len = self.room.len();
+        let start = self.selected_index.min(len - 1);
+        let rank = |card: &Card| {
+            if card.is_monster() {
+                if self.best_damage(card) < self.health { 0 } else { 2 }
+            } else if card.is_weapon() {
+                let upgrade = self.weapon.as_ref().is_none_or(|w| card.rank > w.card.rank);
+                if upgrade { 0 } else { 1 }
+            } else if self.potion_heal(card).is_some_and(|heal| heal > 0) {
+                0
+            } else {
+                1
+            }
+        };
+        (0..len)
+            .map(|step| (start + step) % len)
+            .min_by_key(|&index| rank(&self.room[index]))
+            .unwrap_or(start)
+    }
+
+    /// Monsters not yet faced (still in the dungeon or the room), counted
+    /// by rank from 2 to 14.
+    fn monsters_left_by_rank(&self) -> [usize; 13] {
+        let mut counts = [0; 13];
+        for card in self.dungeon.iter().chain(&self.room).filter(|c| c.is_monster()) {
+            counts[card.rank as usize - 2] += 1;
+        }
+        counts
+    }
+
+    /// Leave the log for whatever it was opened from: the board, or the
+    /// game-over screen once the run has ended.
+    fn close_log(&mut self) {
+        self.screen = if self.game_over { Screen::GameOver } else { Screen::Game };
+    }
+
+    /// The fights the combat modal offers for its monster, in order: the
+    /// held weapon and the spare when they can hit it, then barehanded.
+    /// Back comes after these.
+    fn combat_options(&self) -> Vec<Action> {
+        let Some(index) = self.combat_card_index.filter(|&i| i < self.room.len()) else {
+            return Vec::new();
+        };
+        let card = &self.room[index];
+        let mut options = Vec::new();
+        if self.can_use_weapon_on(card) {
+            options.push(Action::Fight { index, use_weapon: true });
+        }
+        if self.can_use_spare_on(card) {
+            options.push(Action::FightWithSpare(index));
+        }
+        options.push(Action::Fight { index, use_weapon: false });
+        options
+    }
+
+    /// What killing `monster` leaves `weapon` able to do, e.g. "After this,
+    /// weapon hits up to 8; 6 monsters left in reach".
+    fn weapon_outlook(&self, name: &str, weapon: &Weapon, monster: &Card) -> String {
+        let durability = self.settings.durability;
+        let after = Weapon { card: weapon.card, last_monster_slain: Some(monster.value()) };
+        let in_reach = self
+            .dungeon
+            .iter()
+            .chain(&self.room)
+            .filter(|c| c.is_monster() && *c != monster && after.can_use_against(c.value(), durability))
+            .count();
+        match after.max_hittable(durability) {
+            Some(max) => format!("After this, {} hits up to {}; {} monsters left in reach", name, max, in_reach),
+            None => format!("Weapons never dull; all {} monsters left stay in reach", in_reach),
+        }
+    }
+
+    /// Resolve a combat modal option: one of `combat_options`, or cancel.
+    fn choose_combat(&mut self, option: usize) -> Result<(), MoveError> {
+        let chosen = self.combat_options().get(option).copied();
+        if chosen.is_some_and(|action| self.hold_lethal(action)) {
+            return Ok(()); // the modal stays up for the second choice
+        }
+        self.screen = Screen::Game;
+        self.combat_card_index = None;
+        match chosen {
+            Some(action) => self.apply_action(action),
+            None => Ok(()),
+        }
+    }
+
+    /// The final score, next to the bot's on the same dungeon when known.
+    fn score_line(&self) -> String {
+        match self.bot_score {
+            Some(bot) => format!("Final Score - You: {}, Bot: {}", self.calculate_score(), bot),
+            None => format!("Final Score: {}", self.calculate_score()),
         }
     }
 
     fn reset(&mut self) {
-        *self = GameState::new();
+        let events = self.events.take();
+        let log_stream = self.log_stream.take();
+        let assist_mode = self.assist_mode;
+        *self = GameState::new(self.settings.clone());
+        self.assist_mode = assist_mode;
+        self.attach_events(events);
+        self.attach_log(log_stream);
+    }
+
+    /// With `--mulligan`, trade a hopeless first room for a fresh dungeon on
+    /// a new seed. Only once per run, and the run remembers it.
+    fn mulligan(&mut self) {
+        if !self.mulligan_available {
+            return;
+        }
+        let old_seed = self.seed;
+        let events = self.events.take();
+        let log_stream = self.log_stream.take();
+        let assist_mode = self.assist_mode;
+        let seed = rand::thread_rng().r#gen();
+        *self = GameState::with_seed(self.settings.clone(), seed);
+        self.assist_mode = assist_mode;
+        self.mulligan_available = false;
+        self.mulligan_used = true;
+        self.attach_events(events);
+        self.attach_log(log_stream);
+        self.log(LogEvent::Mulligan(old_seed));
+        self.message = format!("Redealt on seed {} (mulligan used)", seed);
+    }
+
+    /// Start over on the same dungeon, even if the seed was picked at random.
+    fn retry_seed(&mut self) {
+        let seed = self.seed;
+        let events = self.events.take();
+        let log_stream = self.log_stream.take();
+        let assist_mode = self.assist_mode;
+        *self = GameState::with_seed(self.settings.clone(), seed);
+        self.assist_mode = assist_mode;
+        self.attach_events(events);
+        self.attach_log(log_stream);
+        self.message = format!("Retrying seed {}", seed);
+    }
+
+    /// Carry the event stream into a fresh game, announcing its first room.
+    fn attach_events(&mut self, events: Option<Box<dyn Write>>) {
+        if events.is_some() {
+            self.events = events;
+            let cards = self.room.clone();
+            let state = self.state_hash();
+            self.emit(GameEvent::RoomDealt { cards, dungeon_left: self.dungeon.len(), state });
+        }
+    }
+
+    /// Write an event line if `--emit-events` is on. A stream that stops
+    /// accepting writes is dropped rather than interrupting the game.
+    fn emit(&mut self, event: GameEvent) {
+        let Some(stream) = self.events.as_mut() else {
+            return;
+        };
+        let line = event.to_json(self.turn_number);
+        if let Err(err) = writeln!(stream, "{}", line) {
+            self.events = None;
+            self.log(LogEvent::Warning(format!("Stopped emitting events: {}", err)));
+        }
+    }
+}
+
+/// Every play of the cards in `room` with these weapons, then a skip if
+/// `can_skip`. Shared by `GameState::legal_moves` and the solver.
+fn legal_moves_in(
+    room: &[Card],
+    weapon: &Option<Weapon>,
+    spare: &Option<Weapon>,
+    can_skip: bool,
+    durability: Durability,
+) -> Vec<Action> {
+    let usable = |weapon: &Option<Weapon>, card: &Card| {
+        weapon.as_ref().is_some_and(|w| w.can_use_against(card.value(), durability))
+    };
+    let mut moves = Vec::new();
+    for (index, card) in room.iter().enumerate() {
+        if card.is_potion() {
+            moves.push(Action::PlayPotion(index));
+        } else if card.is_weapon() {
+            moves.push(Action::PlayWeapon(index));
+        } else {
+            moves.push(Action::Fight { index, use_weapon: false });
+            if usable(weapon, card) {
+                moves.push(Action::Fight { index, use_weapon: true });
+            }
+            if usable(spare, card) {
+                moves.push(Action::FightWithSpare(index));
+            }
+        }
+    }
+    if can_skip {
+        moves.push(Action::Skip);
+    }
+    moves
+}
+
+/// A move in words, e.g. "fight 9♠ with your weapon", against the room it's played in.
+fn describe_move(room: &[Card], action: Action) -> String {
+    match action {
+        Action::PlayPotion(index) => format!("drink {}", room[index].display()),
+        Action::PlayWeapon(index) => format!("equip {}", room[index].display()),
+        Action::Fight { index, use_weapon } => format!(
+            "fight {} {}",
+            room[index].display(),
+            if use_weapon { "with your weapon" } else { "barehanded" }
+        ),
+        Action::FightWithSpare(index) => {
+            format!("fight {} with your spare weapon", room[index].display())
+        }
+        Action::Skip => "skip the room".to_string(),
+    }
+}
+
+/// The built-in strategy: arm up, heal when it won't overflow, skip rooms
+/// that could kill, spend the weapon on the biggest monster it can hit (so it
+/// dulls as little as possible), otherwise take the cheapest fight.
+fn greedy_action(game: &GameState) -> Action {
+    let room = &game.room;
+    let monsters: Vec<usize> = (0..room.len()).filter(|&i| room[i].is_monster()).collect();
+    let best_weapon = (0..room.len()).filter(|&i| room[i].is_weapon()).max_by_key(|&i| room[i].value());
+    let best_potion = (0..room.len()).filter(|&i| room[i].is_potion()).max_by_key(|&i| room[i].value());
+
+    if let Some(w) = best_weapon {
+        // Swap only if the fresh weapon does better against this room's monsters
+        let fresh = room[w].value();
+        let with_fresh: i32 = monsters
+            .iter()
+            .map(|&m| game.settings.combat.damage(room[m].value(), fresh))
+            .sum();
+        let with_held: i32 = monsters.iter().map(|&m| game.best_damage(&room[m])).sum();
+        let upgrade = match game.weapon {
+            None => true,
+            Some(ref held) if monsters.is_empty() => fresh > held.card.value(),
+            Some(_) => with_fresh < with_held,
+        };
+        if upgrade {
+            return Action::PlayWeapon(w);
+        }
+    }
+
+    if let Some(p) = best_potion
+        && !game.potion_would_be_wasted(&room[p])
+        && (game.health + room[p].value() as i32 <= game.max_health
+            || game.health * 2 <= game.max_health)
+    {
+        return Action::PlayPotion(p);
+    }
+
+    // The room costs at least its cheapest plays
+    let mut costs: Vec<i32> = room
+        .iter()
+        .map(|c| if c.is_monster() { game.best_damage(c) } else { 0 })
+        .collect();
+    costs.sort();
+    let worst_case: i32 = costs.iter().take(game.settings.plays_per_room() as usize).sum();
+    if game.can_skip() && worst_case >= game.health {
+        return Action::Skip;
+    }
+
+    let weapon_target = monsters
+        .iter()
+        .copied()
+        .filter(|&i| {
+            (game.can_use_weapon_on(&room[i]) || game.can_use_spare_on(&room[i]))
+                && game.best_damage(&room[i]) < game.health
+        })
+        .max_by_key(|&i| room[i].value());
+    if let Some(index) = weapon_target {
+        return game.best_fight(index);
+    }
+
+    let cheapest = monsters.iter().copied().min_by_key(|&i| room[i].value());
+    if let Some(index) = cheapest
+        && game.best_damage(&room[index]) < game.health
+    {
+        return Action::Fight { index, use_weapon: false };
+    }
+
+    // Only lethal fights left; play anything else first
+    if let Some(p) = best_potion {
+        return Action::PlayPotion(p);
+    }
+    if let Some(w) = best_weapon {
+        return Action::PlayWeapon(w);
+    }
+    let index = cheapest.expect("a room without potions or weapons holds a monster");
+    game.best_fight(index)
+}
+
+/// Play one dungeon to the end with the built-in strategy.
+fn play_greedy(settings: &Settings, seed: u64) -> GameState {
+    let mut game = GameState::with_seed(settings.clone(), seed);
+    game.persist = false;
+    play_out(&mut game);
+    game
+}
+
+fn play_out(game: &mut GameState) {
+    while !game.game_over {
+        game.apply_action(greedy_action(game))
+            .expect("the greedy strategy only picks cards in the room");
+    }
+}
+
+/// Play `settings.games` games with the greedy strategy and print the results.
+fn run_simulation(
+    settings: &Settings,
+    mut events: Option<Box<dyn Write>>,
+    mut log_stream: Option<Box<dyn Write>>,
+) {
+    let mut wins = 0;
+    let mut total_score = 0;
+    for n in 0..settings.games {
+        let seed = match settings.seed {
+            Some(seed) => seed.wrapping_add(n as u64),
+            None => rand::thread_rng().r#gen(),
+        };
+        let mut game = GameState::with_seed(settings.clone(), seed);
+        game.persist = false;
+        game.attach_events(events.take());
+        game.attach_log(log_stream.take());
+        play_out(&mut game);
+        events = game.events.take();
+        log_stream = game.log_stream.take();
+        let score = game.calculate_score();
+        total_score += score;
+        if game.won {
+            wins += 1;
+            println!("Game {} (seed {}): won with {} HP, score {}", n + 1, seed, game.health, score);
+        } else {
+            println!("Game {} (seed {}): died on turn {}, score {}", n + 1, seed, game.turn_number, score);
+        }
+    }
+    if settings.games > 1 {
+        println!(
+            "Won {}/{} · average score {:.1}",
+            wins,
+            settings.games,
+            total_score as f64 / settings.games as f64
+        );
+    }
+}
+
+/// Columns of `runs.csv`, one row appended per finished run.
+const RUNS_CSV_HEADER: &str = "finished,seed,rules,result,score,turns,killed_by,killing_damage,\
+spades_damage,clubs_damage,weapon_kills,barehanded_kills,potions_drunk,mulligan";
+
+/// Quote a CSV field if it holds a comma, quote or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// How many distinct positions `--solve` may remember before it gives up.
+const SOLVER_BUDGET: usize = 4_000_000;
+
+/// Just what decides the rest of a run, for the solver to copy cheaply:
+/// the same rules as `GameState`, without the log, events or UI.
+#[derive(Clone)]
+struct Position {
+    dungeon: Vec<Card>,
+    room: Vec<Card>,
+    health: i32,
+    weapon: Option<Weapon>,
+    spare: Option<Weapon>,
+    played: u8,
+    turn_potion: Option<u8>,
+    just_skipped: bool,
+    last_potion: Option<Card>,
+    skip_penalty: i32,
+    over: bool,
+    won: bool,
+}
+
+impl Position {
+    fn of(game: &GameState) -> Self {
+        Position {
+            dungeon: game.dungeon.clone(),
+            room: game.room.clone(),
+            health: game.health,
+            weapon: game.weapon.clone(),
+            spare: game.spare.clone(),
+            played: game.cards_played_this_turn,
+            turn_potion: game.turn_potion,
+            just_skipped: game.just_skipped,
+            last_potion: game.last_card_was_potion,
+            skip_penalty: game.skip_penalty,
+            over: game.game_over,
+            won: game.won,
+        }
+    }
+
+    /// Same rules as `GameState::skip_blocker`.
+    fn can_skip(&self, settings: &Settings) -> bool {
+        let too_weak = settings.skip_penalty == SkipPenalty::Health && self.health <= SKIP_PENALTY;
+        !self.just_skipped && self.played == 0 && !self.dungeon.is_empty() && !too_weak
+    }
+
+    fn moves(&self, settings: &Settings) -> Vec<Action> {
+        legal_moves_in(&self.room, &self.weapon, &self.spare, self.can_skip(settings), settings.durability)
+    }
+
+    /// The position after `action`, mirroring `GameState::apply_action`.
+    fn after(&self, action: Action, settings: &Settings) -> Position {
+        let mut next = self.clone();
+        match action {
+            Action::PlayPotion(index) => {
+                let card = next.room.remove(index);
+                if settings.potions.counts(card.value(), next.turn_potion) {
+                    if !settings.potions.heals_at_turn_end() {
+                        next.health = (next.health + card.value() as i32).min(settings.max_health);
+                    }
+                    next.turn_potion = Some(card.value());
+                    next.last_potion = Some(card);
+                }
+                next.played += 1;
+                next.finish_play(settings);
+            }
+            Action::PlayWeapon(index) => {
+                let card = next.room.remove(index);
+                if settings.two_weapons && next.spare.is_none() && next.weapon.is_some() {
+                    next.spare = next.weapon.take();
+                }
+                next.weapon = Some(Weapon { card, last_monster_slain: None });
+                next.last_potion = None;
+                next.played += 1;
+                next.finish_play(settings);
+            }
+            Action::Fight { index, use_weapon } => next.fight(index, use_weapon, settings),
+            Action::FightWithSpare(index) => {
+                std::mem::swap(&mut next.weapon, &mut next.spare);
+                next.fight(index, true, settings);
+            }
+            Action::Skip => {
+                match settings.skip_order {
+                    SkipOrder::Front => {
+                        let at = next.dungeon.len().min(4);
+                        next.dungeon.splice(at..at, next.room.drain(..));
+                    }
+                    // Shuffled skips are turned away by Settings::load
+                    SkipOrder::Bottom | SkipOrder::Shuffle => next.dungeon.append(&mut next.room),
+                }
+                next.just_skipped = true;
+                match settings.skip_penalty {
+                    SkipPenalty::Off => {}
+                    SkipPenalty::Health => next.health -= SKIP_PENALTY,
+                    SkipPenalty::Score => next.skip_penalty += SKIP_PENALTY,
+                }
+                next.deal();
+            }
+        }
+        next
+    }
+
+    fn fight(&mut self, index: usize, use_weapon: bool, settings: &Settings) {
+        let card = self.room.remove(index);
+        let damage = match self.weapon.as_mut() {
+            Some(weapon) if use_weapon => {
+                weapon.last_monster_slain = Some(card.value());
+                settings.combat.damage(card.value(), weapon.card.value())
+            }
+            _ => card.value() as i32,
+        };
+        self.health -= damage;
+        self.last_potion = None;
+        self.played += 1;
+        if self.health <= 0 {
+            self.health = 0;
+            self.over = true;
+        } else {
+            self.finish_play(settings);
+        }
+    }
+
+    fn finish_play(&mut self, settings: &Settings) {
+        let cleared = self.dungeon.is_empty() && self.room.is_empty();
+        if self.played < settings.plays_per_room() && !cleared {
+            return;
+        }
+        if settings.potions.heals_at_turn_end()
+            && let Some(value) = self.turn_potion.take()
+        {
+            self.health = (self.health + value as i32).min(settings.max_health);
+        }
+        if self.dungeon.is_empty() && self.room.len() == 1 {
+            self.played = 0;
+            self.turn_potion = None;
+        } else if cleared {
+            self.over = true;
+            self.won = true;
+        } else {
+            self.just_skipped = false;
+            self.deal();
+        }
+    }
+
+    fn deal(&mut self) {
+        while self.room.len() < 4 && !self.dungeon.is_empty() {
+            self.room.push(self.dungeon.remove(0));
+        }
+        self.played = 0;
+        self.turn_potion = None;
+        self.last_potion = None;
+    }
+
+    /// Settled without searching: `Some(true)` when even fighting every
+    /// monster left barehanded can't kill, `Some(false)` when the least
+    /// damage they could deal against the best weapon left outruns every
+    /// potion left.
+    fn bound(&self, settings: &Settings) -> Option<bool> {
+        let left = || self.dungeon.iter().chain(self.room.iter());
+        let monsters = left().filter(|c| c.is_monster()).map(|c| c.value() as i32);
+        if self.health > monsters.sum() {
+            return Some(true);
+        }
+        let strongest = left()
+            .filter(|c| c.is_weapon())
+            .chain(self.weapon.iter().chain(self.spare.iter()).map(|w| &w.card))
+            .map(|c| c.value())
+            .max()
+            .unwrap_or(0);
+        let least_damage: i32 = left()
+            .filter(|c| c.is_monster())
+            .map(|c| settings.combat.damage(c.value(), strongest))
+            .sum();
+        let healing: i32 = left().filter(|c| c.is_potion()).map(|c| c.value() as i32).sum();
+        (self.health + healing <= least_damage).then_some(false)
+    }
+
+    /// Same formula as `GameState::calculate_score`.
+    fn score(&self, settings: &Settings) -> i32 {
+        if self.won {
+            let bonus = match self.last_potion {
+                Some(potion) if settings.classic_bonus && self.health == settings.max_health => {
+                    potion.value() as i32
+                }
+                _ => 0,
+            };
+            self.health + bonus - self.skip_penalty
+        } else {
+            let remaining: i32 = self
+                .dungeon
+                .iter()
+                .chain(self.room.iter())
+                .filter(|c| c.is_monster())
+                .map(|c| c.value() as i32)
+                .sum();
+            self.health - remaining - self.skip_penalty
+        }
+    }
+
+    /// Everything but health that two positions must share to play out the
+    /// same, with the room sorted since the order of its cards doesn't matter.
+    fn key(&self) -> Vec<u8> {
+        let code = |card: &Card| card.suit as u8 * 16 + card.rank;
+        let weapon = |w: &Option<Weapon>| match w {
+            Some(w) => [code(&w.card), w.last_monster_slain.unwrap_or(0)],
+            None => [0, 0],
+        };
+        let mut room: Vec<u8> = self.room.iter().map(code).collect();
+        room.sort();
+        let mut key = vec![self.played, self.turn_potion.unwrap_or(0), self.just_skipped as u8];
+        key.extend(weapon(&self.weapon));
+        key.extend(weapon(&self.spare));
+        key.push(self.last_potion.as_ref().map_or(0, code));
+        key.push(self.skip_penalty as u8);
+        key.extend(room);
+        key.push(0xff);
+        key.extend(self.dungeon.iter().map(code));
+        key
+    }
+}
+
+/// Exhaustive search over positions, remembering each one it has settled.
+/// Both searches return `None` once their memo outgrows `SOLVER_BUDGET`.
+struct Solver<'a> {
+    settings: &'a Settings,
+    // Per position bar health: the most HP known to lose and the least
+    // known to win, since more health never hurts
+    outcomes: HashMap<Vec<u8>, (i32, i32)>,
+    scores: HashMap<Vec<u8>, i32>,
+}
+
+impl Solver<'_> {
+    fn new(settings: &Settings) -> Solver<'_> {
+        Solver { settings, outcomes: HashMap::new(), scores: HashMap::new() }
+    }
+
+    /// The moves worth trying first: cheap plays before costly ones, and
+    /// skipping last.
+    fn ordered_moves(&self, position: &Position) -> Vec<(Action, Position)> {
+        let mut moves: Vec<(Action, Position)> = position
+            .moves(self.settings)
+            .into_iter()
+            .map(|action| (action, position.after(action, self.settings)))
+            .collect();
+        moves.sort_by_key(|(action, next)| (*action == Action::Skip, position.health - next.health));
+        moves
+    }
+
+    /// Whether some line from here clears the dungeon.
+    fn wins(&mut self, position: &Position) -> Option<bool> {
+        if position.over {
+            return Some(position.won);
+        }
+        if let Some(settled) = position.bound(self.settings) {
+            return Some(settled);
+        }
+        let key = position.key();
+        let (lose_max, win_min) = self.outcomes.get(&key).copied().unwrap_or((0, i32::MAX));
+        if position.health <= lose_max {
+            return Some(false);
+        }
+        if position.health >= win_min {
+            return Some(true);
+        }
+        if self.outcomes.len() >= SOLVER_BUDGET {
+            return None;
+        }
+        for (_, next) in self.ordered_moves(position) {
+            if self.wins(&next)? {
+                self.outcomes.insert(key, (lose_max, position.health));
+                return Some(true);
+            }
+        }
+        self.outcomes.insert(key, (position.health, win_min));
+        Some(false)
+    }
+
+    /// The best score reachable from here.
+    fn best(&mut self, position: &Position) -> Option<i32> {
+        if position.over {
+            return Some(position.score(self.settings));
+        }
+        let mut key = position.key();
+        key.push(position.health as u8);
+        if let Some(&score) = self.scores.get(&key) {
+            return Some(score);
+        }
+        if self.scores.len() >= SOLVER_BUDGET {
+            return None;
+        }
+        let mut best = i32::MIN;
+        for action in position.moves(self.settings) {
+            best = best.max(self.best(&position.after(action, self.settings))?);
+        }
+        self.scores.insert(key, best);
+        Some(best)
+    }
+
+    /// One line of moves from `position` along which `keeps` holds, given
+    /// that it holds for `position` itself, and whether it reaches the end
+    /// of the run. It can stop short once the memo is full: `keeps` then
+    /// can't settle the positions after it.
+    fn line(
+        &mut self,
+        position: &Position,
+        mut keeps: impl FnMut(&mut Self, &Position) -> bool,
+    ) -> (Vec<(Position, Action)>, bool) {
+        let mut line = Vec::new();
+        let mut position = position.clone();
+        while !position.over {
+            let action = position
+                .moves(self.settings)
+                .into_iter()
+                .find(|&a| keeps(self, &position.after(a, self.settings)));
+            let Some(action) = action else {
+                return (line, false);
+            };
+            let next = position.after(action, self.settings);
+            line.push((position, action));
+            position = next;
+        }
+        (line, true)
+    }
+}
+
+/// `--solve`: say whether perfect play wins the seed's dungeon, and how.
+fn run_solver(settings: &Settings, seed: u64) {
+    let mut game = GameState::with_seed(settings.clone(), seed);
+    game.persist = false;
+    let start = Position::of(&game);
+    let mut solver = Solver::new(settings);
+    let (line, complete) = match solver.wins(&start) {
+        None => {
+            println!("Seed {}: gave up after {} positions without settling it", seed, SOLVER_BUDGET);
+            return;
+        }
+        Some(true) => {
+            println!("Seed {}: winnable", seed);
+            solver.line(&start, |solver, next| solver.wins(next) == Some(true))
+        }
+        Some(false) => match solver.best(&start) {
+            Some(best) => {
+                println!("Seed {}: unwinnable (best score {})", seed, best);
+                solver.line(&start, |solver, next| solver.best(next) == Some(best))
+            }
+            None => {
+                println!("Seed {}: unwinnable (best score not settled in {} positions)", seed, SOLVER_BUDGET);
+                return;
+            }
+        },
+    };
+    let mut room = 0;
+    for (position, action) in line {
+        if position.played == 0 {
+            room += 1;
+            let cards: Vec<String> = position.room.iter().map(|c| c.display()).collect();
+            println!("Room {}: {} ({} HP)", room, cards.join(" "), position.health);
+        }
+        println!("  {}", describe_move(&position.room, action));
+    }
+    if !complete {
+        println!("  ... (the rest of the line wasn't settled within {} positions)", SOLVER_BUDGET);
     }
 }
 
 fn main() -> Result<(), io::Error> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let (settings, warnings) = match Settings::load(args) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let glyph_fallback = use_ascii_glyphs(settings.ascii);
+    let palette_fallback = use_palette(settings.colors);
+    NUMERIC_RANKS.store(settings.numeric_ranks, Ordering::Relaxed);
+
+    let open = |path: Option<&str>, what: &str| match path.map(open_event_stream) {
+        Some(Ok(stream)) => Some(stream),
+        Some(Err(err)) => {
+            eprintln!("Couldn't open {}: {}", what, err);
+            std::process::exit(2);
+        }
+        None => None,
+    };
+    let events = open(settings.emit_events.as_deref(), "event stream");
+    let log_stream = open(settings.log_json.as_deref(), "JSON log");
+
+    if settings.solve.is_some() || settings.simulate {
+        for warning in &warnings {
+            eprintln!("{}", warning);
+        }
+    }
+    if let Some(seed) = settings.solve {
+        run_solver(&settings, seed);
+        return Ok(());
+    }
+    if settings.simulate {
+        run_simulation(&settings, events, log_stream);
+        return Ok(());
+    }
+
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        eprintln!("Scoundrel requires an interactive terminal; use --simulate for headless runs");
+        std::process::exit(1);
+    }
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    if settings.altscreen {
+        execute!(stdout, EnterAlternateScreen)?;
+    }
+    execute!(stdout, EnableMouseCapture)?;
+    // Where the terminal can tell presses from auto-repeat, ask it to, so a held
+    // action key only acts once. Elsewhere run_app falls back to a debounce.
+    let enhanced_keys = supports_keyboard_enhancement().unwrap_or(false);
+    if enhanced_keys {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
     let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let terminal = if settings.altscreen {
+        Terminal::new(backend)
+    } else {
+        // As tall as the terminal, so the board has the room it would have
+        // on the alternate screen; the scrollback above it is kept
+        crossterm::terminal::size().and_then(|(_, rows)| {
+            Terminal::with_options(backend, TerminalOptions { viewport: Viewport::Inline(rows) })
+        })
+    };
+    let mut terminal = match terminal {
+        Ok(terminal) => terminal,
+        Err(err) => {
+            // e.g. an inline viewport in a terminal that won't report the cursor
+            restore_terminal(settings.altscreen, enhanced_keys)?;
+            return Err(err);
+        }
+    };
 
-    let mut game = GameState::new();
+    // A held key can only be followed where its release is reported
+    let hold_unsupported = settings.hold_repeat && !enhanced_keys;
+    let mut game = GameState::new(settings);
+    // Each of these replaces the message before it, starting with the
+    // dungeon's difficulty rating (which stays in the log)
+    if let Some(reason) = glyph_fallback {
+        game.message = format!("Drawing suits as letters: {} (--ascii silences this)", reason);
+    } else if let Some(reason) = palette_fallback
+        && game.settings.theme == Theme::Standard
+    {
+        game.message = format!("Fewer colors: {} (--colors full overrides)", reason);
+    }
+    if hold_unsupported {
+        game.settings.hold_repeat = false;
+        game.message = "This terminal doesn't report key releases; --hold-repeat is off".to_string();
+    }
+    game.attach_events(events);
+    let mut log_stream = log_stream;
+    if let Some(warning) = warnings.first() {
+        game.message = warning.clone();
+    }
+    for warning in warnings {
+        game.log(LogEvent::Warning(warning));
+    }
+    if game.settings.autosave {
+        match Checkpoint::load() {
+            Ok(Some(checkpoint)) if checkpoint.rules == Checkpoint::rules_of(&game.settings) => {
+                game.pending_resume = Some(checkpoint);
+                game.screen = Screen::Resume;
+                // Held unwritten; answer_resume replays whichever run is kept
+                game.log_stream = log_stream.take();
+                if game.settings.ironman {
+                    // Starting fresh would throw the run away, so there's no asking
+                    game.answer_resume(true);
+                }
+            }
+            Ok(Some(_)) => game.message = "Saved run uses other rules - starting fresh".to_string(),
+            Ok(None) => {}
+            Err(err) => game.message = err.to_string(),
+        }
+    }
+    if log_stream.is_some() {
+        game.attach_log(log_stream);
+    }
     let result = run_app(&mut terminal, &mut game);
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
+    if !game.settings.altscreen {
+        // Leave the last frame where it is and carry on below it
+        let bottom = terminal.get_frame().area().bottom();
+        terminal.set_cursor_position((0, bottom.saturating_sub(1)))?;
+    }
+    restore_terminal(game.settings.altscreen, enhanced_keys)?;
+    if !game.settings.altscreen {
+        println!();
+    }
     terminal.show_cursor()?;
 
     if let Err(err) = result {
         println!("Error: {:?}", err);
+    } else if game.settings.ironman && game.game_over {
+        let outcome = if game.won { "won" } else { "died" };
+        println!("Ironman run {}: score {} on seed {}", outcome, game.calculate_score(), game.seed);
     }
 
     Ok(())
 }
 
+/// Undo what `main` set up around `run_app`: raw mode, mouse capture, the
+/// keyboard enhancement and the alternate screen.
+fn restore_terminal(altscreen: bool, enhanced_keys: bool) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    if enhanced_keys {
+        execute!(stdout, PopKeyboardEnhancementFlags)?;
+    }
+    disable_raw_mode()?;
+    if altscreen {
+        execute!(stdout, LeaveAlternateScreen)?;
+    }
+    execute!(stdout, DisableMouseCapture, crossterm::cursor::Show)
+}
+
+/// Where `--emit-events` and `--log-json` write: stdout for `-`, otherwise a file, FIFO or
+/// `/dev/fd/N`. Lines are flushed as they're written so watchers see them live.
+fn open_event_stream(path: &str) -> io::Result<Box<dyn Write>> {
+    if path == "-" {
+        return Ok(Box::new(io::stdout()));
+    }
+    Ok(Box::new(io::LineWriter::new(File::create(path)?)))
+}
+
+/// A second press of the same action key inside this window is treated as key
+/// repeat and dropped, so holding Enter can't play two cards at once.
+const ACTION_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Keys that play cards or skip, where an accidental repeat can't be undone.
+fn is_action_key(code: KeyCode) -> bool {
+    matches!(
+        code,
+        KeyCode::Enter | KeyCode::Char(' ') | KeyCode::Char('s') | KeyCode::Char('1'..='4')
+    )
+}
+
+/// Which way a Left/Right/Tab key steps the selection (true = right).
+fn nav_direction(code: KeyCode) -> Option<bool> {
+    match code {
+        KeyCode::Right | KeyCode::Tab => Some(true),
+        KeyCode::Left | KeyCode::BackTab => Some(false),
+        _ => None,
+    }
+}
+
+/// How often timed effects advance while waiting for input.
+const TICK_RATE: Duration = Duration::from_millis(100);
+
 fn run_app<B: ratatui::backend::Backend>(
     terminal: &mut Terminal<B>,
     game: &mut GameState,
 ) -> io::Result<()> {
+    let mut last_action: Option<(KeyCode, Instant)> = None;
+    let mut last_tick = Instant::now();
+    // Redraw after input and on each tick, not per event: a drag-resize can
+    // send a storm of Resize events, and the next tick lays out the last size
+    let mut dirty = true;
     loop {
-        terminal.draw(|f| ui(f, &mut *game))?;
-
-        match event::read()? {
-            Event::Mouse(mouse) => {
-                if mouse.kind == MouseEventKind::Down(MouseButton::Left) {
-                    let x = mouse.column;
-                    let y = mouse.row;
-
-                    match game.screen {
-                        Screen::Game => {
-                            // Check if click is on a card
-                            for (idx, area) in game.card_areas.iter().enumerate() {
-                                if x >= area.x && x < area.x + area.width
-                                    && y >= area.y && y < area.y + area.height {
-                                    if idx < game.room.len() {
-                                        game.selected_index = idx;
-                                        let card = &game.room[idx];
-                                        if card.is_potion() {
-                                            game.play_potion(idx);
-                                        } else if card.is_weapon() {
-                                            game.play_weapon(idx);
-                                        } else {
-                                            if game.weapon.is_none() {
-                                                game.fight_monster(idx, false);
-                                            } else {
-                                                game.combat_card_index = Some(idx);
-                                                game.combat_selection = 0;
-                                                game.screen = Screen::Combat;
-                                            }
-                                        }
-                                    }
-                                    break;
-                                }
-                            }
-                        }
-                        Screen::Combat => {
-                            // Check if click is on a combat button
-                            for (idx, area) in game.combat_button_areas.iter().enumerate() {
-                                if x >= area.x && x < area.x + area.width
-                                    && y >= area.y && y < area.y + area.height {
-                                    let card_idx = game.combat_card_index.unwrap();
-                                    let card = &game.room[card_idx];
-                                    let can_use_weapon = game.can_use_weapon_on(card);
-
-                                    if can_use_weapon {
-                                        match idx {
-                                            0 => {
-                                                game.fight_monster(card_idx, true);
-                                                game.screen = Screen::Game;
-                                            }
-                                            1 => {
-                                                game.fight_monster(card_idx, false);
-                                                game.screen = Screen::Game;
-                                            }
-                                            _ => game.screen = Screen::Game,
-                                        }
-                                    } else {
-                                        match idx {
-                                            0 => {
-                                                game.fight_monster(card_idx, false);
-                                                game.screen = Screen::Game;
-                                            }
-                                            _ => game.screen = Screen::Game,
-                                        }
-                                    }
-                                    game.combat_card_index = None;
-                                    break;
-                                }
-                            }
-                        }
-                        Screen::Help | Screen::Log => {
-                            game.screen = Screen::Game;
-                        }
-                        Screen::ConfirmQuit => {
-                            game.screen = Screen::Game;
-                        }
-                        _ => {}
-                    }
-                }
-            }
-            Event::Key(key) => {
-            if key.kind != KeyEventKind::Press {
-                continue;
-            }
+        if last_tick.elapsed() >= TICK_RATE {
+            game.on_tick();
+            last_tick = Instant::now();
+            dirty = true;
+        }
 
-            match game.screen {
-                Screen::Game => match key.code {
-                    KeyCode::Char('q') => game.screen = Screen::ConfirmQuit,
-                    KeyCode::Char('?') => game.screen = Screen::Help,
-                    KeyCode::Char('l') => game.screen = Screen::Log,
-                    KeyCode::Char('s') => game.skip_room(),
-                    KeyCode::Tab | KeyCode::Right => {
-                        if !game.room.is_empty() {
-                            game.selected_index = (game.selected_index + 1) % game.room.len();
-                        }
-                    }
-                    KeyCode::BackTab | KeyCode::Left => {
-                        if !game.room.is_empty() {
-                            game.selected_index = if game.selected_index == 0 {
-                                game.room.len() - 1
-                            } else {
-                                game.selected_index - 1
-                            };
-                        }
-                    }
-                    KeyCode::Down => {
-                        if game.selected_index + 2 < game.room.len() {
-                            game.selected_index += 2;
-                        }
-                    }
-                    KeyCode::Up => {
-                        if game.selected_index >= 2 {
-                            game.selected_index -= 2;
-                        }
+        if game.game_over && game.bot_score.is_none() {
+            game.bot_score = Some(play_greedy(&game.settings, game.seed).calculate_score());
+        }
+
+        if dirty {
+            terminal.draw(|f| ui(f, &mut *game))?;
+            dirty = false;
+        }
+
+        if !event::poll(TICK_RATE.saturating_sub(last_tick.elapsed()))? {
+            continue;
+        }
+
+        let event = event::read()?;
+        if matches!(event, Event::Resize(..)) {
+            continue;
+        }
+        dirty = true;
+        match event {
+            Event::Mouse(mouse) if mouse.kind == MouseEventKind::Down(MouseButton::Left) => {
+                game.message_since = game.ticks;
+                let x = mouse.column;
+                let y = mouse.row;
+
+                match game.screen {
+                    // As with a key, a click finishes dealing the room
+                    Screen::Game if game.dealing.is_some() => game.dealing = None,
+                    Screen::Game if game.weapon_area.contains((x, y).into()) => {
+                        game.screen = Screen::DullingHelp;
                     }
-                    KeyCode::Enter | KeyCode::Char(' ') => {
-                        if game.selected_index < game.room.len() {
-                            let card = &game.room[game.selected_index];
-                            if card.is_potion() {
-                                game.play_potion(game.selected_index);
-                            } else if card.is_weapon() {
-                                game.play_weapon(game.selected_index);
-                            } else {
-                                // Monster - if no weapon, attack directly
-                                if game.weapon.is_none() {
-                                    game.fight_monster(game.selected_index, false);
-                                } else {
-                                    // Has weapon - show combat options
-                                    game.combat_card_index = Some(game.selected_index);
-                                    game.combat_selection = 0;
-                                    game.screen = Screen::Combat;
+                    Screen::Game => {
+                        // Check if click is on a card
+                        for (idx, area) in game.card_areas.iter().enumerate() {
+                            if x >= area.x && x < area.x + area.width
+                                && y >= area.y && y < area.y + area.height {
+                                if idx < game.room.len()
+                                    && let Err(err) = game.play_selected(idx)
+                                {
+                                    game.message = err.to_string();
                                 }
+                                break;
                             }
                         }
                     }
-                    KeyCode::Char(c) if c >= '1' && c <= '4' => {
-                        let idx = (c as usize) - ('1' as usize);
-                        if idx < game.room.len() {
-                            game.selected_index = idx;
-                            let card = &game.room[idx];
-                            if card.is_potion() {
-                                game.play_potion(idx);
-                            } else if card.is_weapon() {
-                                game.play_weapon(idx);
-                            } else {
-                                // Monster - if no weapon, attack directly
-                                if game.weapon.is_none() {
-                                    game.fight_monster(idx, false);
-                                } else {
-                                    game.combat_card_index = Some(idx);
-                                    game.combat_selection = 0;
-                                    game.screen = Screen::Combat;
+                    Screen::Combat => {
+                        // Check if click is on a combat button
+                        for (idx, area) in game.combat_button_areas.iter().enumerate() {
+                            if x >= area.x && x < area.x + area.width
+                                && y >= area.y && y < area.y + area.height {
+                                if let Err(err) = game.choose_combat(idx) {
+                                    game.message = err.to_string();
                                 }
+                                break;
                             }
                         }
                     }
-                    _ => {}
-                },
-                Screen::Combat => {
-                    let card_idx = game.combat_card_index.unwrap();
-                    let card = &game.room[card_idx];
-                    let can_use_weapon = game.can_use_weapon_on(card);
-                    let num_options = if can_use_weapon { 3 } else { 2 };
-
-                    match key.code {
-                        KeyCode::Up | KeyCode::BackTab => {
-                            game.combat_selection = if game.combat_selection == 0 {
-                                num_options - 1
-                            } else {
-                                game.combat_selection - 1
-                            };
-                        }
-                        KeyCode::Down | KeyCode::Tab => {
-                            game.combat_selection = (game.combat_selection + 1) % num_options;
-                        }
-                        KeyCode::Enter | KeyCode::Char(' ') => {
-                            if can_use_weapon {
-                                match game.combat_selection {
-                                    0 => {
-                                        game.fight_monster(card_idx, true);
-                                        game.screen = Screen::Game;
-                                    }
-                                    1 => {
-                                        game.fight_monster(card_idx, false);
-                                        game.screen = Screen::Game;
-                                    }
-                                    _ => game.screen = Screen::Game,
-                                }
-                            } else {
-                                match game.combat_selection {
-                                    0 => {
-                                        game.fight_monster(card_idx, false);
-                                        game.screen = Screen::Game;
-                                    }
-                                    _ => game.screen = Screen::Game,
-                                }
-                            }
-                            game.combat_card_index = None;
-                        }
-                        KeyCode::Char('1') => {
-                            if can_use_weapon {
-                                game.fight_monster(card_idx, true);
-                            } else {
-                                game.fight_monster(card_idx, false);
-                            }
-                            game.screen = Screen::Game;
-                            game.combat_card_index = None;
-                        }
-                        KeyCode::Char('2') if can_use_weapon => {
-                            game.fight_monster(card_idx, false);
-                            game.screen = Screen::Game;
-                            game.combat_card_index = None;
-                        }
-                        KeyCode::Char('b') | KeyCode::Esc => {
-                            game.screen = Screen::Game;
-                            game.combat_card_index = None;
-                        }
-                        _ => {}
+                    Screen::Log => game.close_log(),
+                    Screen::Help
+                    | Screen::CardHelp
+                    | Screen::DullingHelp
+                    | Screen::Export
+                    | Screen::Stats
+                    | Screen::Graveyard
+                    | Screen::Histogram => {
+                        game.screen = Screen::Game;
+                    }
+                    Screen::ConfirmQuit => {
+                        game.screen = Screen::Game;
                     }
+                    Screen::Results => game.screen = Screen::GameOver,
+                    Screen::TurnSummary => game.screen = Screen::Game,
+                    _ => {}
                 }
-                Screen::Help => {
-                    game.screen = Screen::Game;
+            }
+            // The guard does the work; a key that doesn't quit falls through
+            Event::Key(key) if handle_key(game, key, &mut last_action) => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+/// Act on one key event. Returns true when the key quits.
+fn handle_key(game: &mut GameState, key: KeyEvent, last_action: &mut Option<(KeyCode, Instant)>) -> bool {
+    // A leaderboard name is typed as is, capitals and all
+    let key = match game.leaderboard_name {
+        Some(_) => key,
+        None => KeyEvent { code: game.settings.keys.translate(game.screen, key.code), ..key },
+    };
+    let playing = matches!(game.screen, Screen::Game | Screen::Combat | Screen::ConfirmSkip);
+    match key.kind {
+        KeyEventKind::Release => {
+            if nav_direction(key.code).is_some() {
+                game.held = None;
+            }
+            return false;
+        }
+        // Held keys repeat as usual, except the ones that play or skip
+        KeyEventKind::Repeat if playing && is_action_key(key.code) => return false,
+        // --hold-repeat already steps a held arrow on each tick
+        KeyEventKind::Repeat if game.held.is_some() && nav_direction(key.code).is_some() => return false,
+        KeyEventKind::Repeat => {}
+        KeyEventKind::Press => {
+            game.held = nav_direction(key.code)
+                .filter(|_| game.settings.hold_repeat && game.screen == Screen::Game)
+                .map(|forward| (forward, game.ticks));
+        }
+    }
+    game.message_since = game.ticks;
+    // A key cuts the deal short, and only that, so nothing is
+    // played before it's been seen
+    if game.dealing.is_some() && game.screen == Screen::Game {
+        game.dealing = None;
+        return false;
+    }
+
+    if playing && is_action_key(key.code) {
+        let now = Instant::now();
+        let repeated = last_action
+            .is_some_and(|(code, at)| code == key.code && now - at < ACTION_DEBOUNCE);
+        // Refresh even when dropped so a held key stays suppressed
+        *last_action = Some((key.code, now));
+        if repeated {
+            return false;
+        }
+    }
+
+    if key.code != KeyCode::Char('a') {
+        game.auto_pending = None;
+    }
+
+    match game.screen {
+        Screen::Game => match key.code {
+            KeyCode::Char('q') if game.settings.confirm_quit => game.screen = Screen::ConfirmQuit,
+            KeyCode::Char('Q') if game.settings.safe => game.screen = Screen::ConfirmQuit,
+            KeyCode::Char('q') | KeyCode::Char('Q') => return true,
+            KeyCode::Char('a') if game.settings.auto_advance => game.auto_advance(),
+            KeyCode::Char('?') if game.room.get(game.selected_index).is_some() => {
+                game.screen = Screen::CardHelp
+            }
+            KeyCode::Char('?') => game.screen = Screen::Help,
+            KeyCode::Char('d') => game.screen = Screen::DullingHelp,
+            KeyCode::Char('l') => game.screen = Screen::Log,
+            KeyCode::Char('e') => game.show_analysis = !game.show_analysis,
+            KeyCode::Char('i') => {
+                game.assist_mode = !game.assist_mode;
+                game.message = if game.assist_mode { "Assists on" } else { "Assists off" }.to_string();
+            }
+            KeyCode::Char('x') => game.screen = Screen::Export,
+            KeyCode::Char('t') => game.screen = Screen::Stats,
+            KeyCode::Char('w') => game.screen = Screen::Graveyard,
+            KeyCode::Char('g') => game.screen = Screen::Histogram,
+            KeyCode::Char('u') => game.undo(),
+            KeyCode::Char('p') => game.screen = Screen::Paused,
+            KeyCode::Char('k') => game.settings.expanded_controls = !game.settings.expanded_controls,
+            KeyCode::Char('c') => {
+                game.settings.theme = game.settings.theme.next();
+                game.message = format!("Theme: {} - C for the next", game.settings.theme.name());
+            }
+            KeyCode::Char('m') if game.mulligan_available => game.screen = Screen::ConfirmMulligan,
+            KeyCode::Char('`') if game.settings.debug => {
+                game.debug_input.clear();
+                game.screen = Screen::DebugPrompt;
+            }
+            KeyCode::Char('s') if game.settings.confirm_endgame_skip
+                && game.can_skip()
+                && game.skip_is_endgame() =>
+            {
+                game.screen = Screen::ConfirmSkip;
+            }
+            KeyCode::Char('s') => {
+                if let Err(err) = game.apply_action(Action::Skip) {
+                    game.message = err.to_string();
                 }
-                Screen::Log => {
-                    game.screen = Screen::Game;
+            }
+            KeyCode::Tab | KeyCode::Right => game.move_selection(true),
+            KeyCode::Char('n') => game.select_next(|_, card| card.is_monster(), "No monsters in this room"),
+            KeyCode::Char('N') => game.select_next(
+                |g, card| !card.is_monster() || g.best_damage(card) < g.health,
+                "Every card left here is lethal",
+            ),
+            KeyCode::BackTab | KeyCode::Left => game.move_selection(false),
+            KeyCode::Down if game.selected_index + 2 < game.room.len() => {
+                game.selected_index += 2;
+            }
+            KeyCode::Up if game.selected_index >= 2 => {
+                game.selected_index -= 2;
+            }
+            KeyCode::Enter | KeyCode::Char(' ') if !game.room.is_empty() => {
+                if let Err(err) = game.play_selected(game.selected_index) {
+                    game.message = err.to_string();
                 }
-                Screen::GameOver => match key.code {
-                    KeyCode::Char('y') | KeyCode::Enter => {
-                        game.reset();
-                    }
-                    KeyCode::Char('n') | KeyCode::Char('q') | KeyCode::Esc => {
-                        return Ok(());
-                    }
-                    _ => {}
-                },
-                Screen::ConfirmQuit => match key.code {
-                    KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Char('y') | KeyCode::Char('Y') => {
-                        return Ok(());
+            }
+            KeyCode::Char(c) if ('1'..='4').contains(&c) => {
+                let idx = (c as usize) - ('1' as usize);
+                if let Err(err) = game.play_selected(idx) {
+                    game.message = err.to_string();
+                }
+            }
+            KeyCode::Char('0'..='9') => {
+                game.message = format!("Press 1-{} to play a card", game.room.len().max(1));
+            }
+            _ => {}
+        },
+        Screen::Combat => {
+            let fights = game.combat_options().len();
+            let num_options = fights + 1; // and Back
+
+            match key.code {
+                KeyCode::Up | KeyCode::BackTab => {
+                    game.combat_selection = if game.combat_selection == 0 {
+                        num_options - 1
+                    } else {
+                        game.combat_selection - 1
+                    };
+                }
+                KeyCode::Down | KeyCode::Tab => {
+                    game.combat_selection = (game.combat_selection + 1) % num_options;
+                }
+                KeyCode::Enter | KeyCode::Char(' ') => {
+                    let option = game.combat_selection;
+                    if let Err(err) = game.choose_combat(option) {
+                        game.message = err.to_string();
                     }
-                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc | _ => {
-                        game.screen = Screen::Game;
+                }
+                KeyCode::Char(c @ '1'..='3') if ((c as u8 - b'1') as usize) < fights => {
+                    if let Err(err) = game.choose_combat((c as u8 - b'1') as usize) {
+                        game.message = err.to_string();
                     }
-                },
+                }
+                KeyCode::Char('b') | KeyCode::Esc => {
+                    game.screen = Screen::Game;
+                    game.combat_card_index = None;
+                }
+                // A stray key only gets a reminder; the fight waits for a real choice
+                KeyCode::Char(_) => {
+                    game.message = format!("Press 1-{} to fight, or B to back out", fights);
+                }
+                _ => {}
+            }
+        }
+        Screen::Help => {
+            game.screen = Screen::Game;
+        }
+        Screen::CardHelp | Screen::DullingHelp => {
+            game.screen = if key.code == KeyCode::Char('?') { Screen::Help } else { Screen::Game };
+        }
+        Screen::Log => match key.code {
+            KeyCode::Char('1') => game.log_filter = LogFilter::Combat,
+            KeyCode::Char('2') => game.log_filter = LogFilter::Potions,
+            KeyCode::Char('3') => game.log_filter = LogFilter::All,
+            _ => game.close_log(),
+        },
+        Screen::Export
+        | Screen::Stats
+        | Screen::Graveyard
+        | Screen::Histogram
+        | Screen::TurnSummary => {
+            game.screen = Screen::Game;
+        }
+        Screen::Results => match key.code {
+            KeyCode::Down => game.results_scroll = game.results_scroll.saturating_add(1),
+            KeyCode::Up => game.results_scroll = game.results_scroll.saturating_sub(1),
+            KeyCode::Enter | KeyCode::Char(' ') | KeyCode::Esc => {
+                game.screen = Screen::GameOver;
+            }
+            _ => {}
+        },
+        Screen::GameOver if game.leaderboard_name.is_some() => match key.code {
+            KeyCode::Enter => game.submit_leaderboard(),
+            KeyCode::Esc => game.leaderboard_name = None,
+            KeyCode::Backspace => {
+                game.leaderboard_name.as_mut().map(String::pop);
+            }
+            KeyCode::Char(c) if !c.is_control() => {
+                if let Some(name) = game.leaderboard_name.as_mut()
+                    && name.chars().count() < LEADERBOARD_NAME_LEN
+                {
+                    name.push(c);
+                }
+            }
+            _ => {}
+        },
+        Screen::GameOver => match key.code {
+            KeyCode::Char('l') => game.screen = Screen::Log,
+            // The run is already recorded; there's no second one
+            _ if game.settings.ironman => return true,
+            KeyCode::Char('y') | KeyCode::Enter => {
+                game.reset();
+            }
+            KeyCode::Char('r') => {
+                game.retry_seed();
+            }
+            KeyCode::Char('n') | KeyCode::Char('q') | KeyCode::Esc => {
+                return true;
+            }
+            // The final message stays up, so other keys are dropped silently
+            _ => {}
+        },
+        Screen::Resume => match key.code {
+            KeyCode::Char('y') | KeyCode::Enter => game.answer_resume(true),
+            KeyCode::Char('n') | KeyCode::Esc => game.answer_resume(false),
+            // Nothing is dealt until the question is answered
+            _ => {}
+        },
+        Screen::Paused => {
+            // Only P resumes, so a bumped key can't end the pause early
+            if key.code == KeyCode::Char('p') {
+                game.screen = Screen::Game;
+            }
+        }
+        Screen::DebugPrompt => match key.code {
+            KeyCode::Enter => {
+                game.screen = Screen::Game;
+                let command = std::mem::take(&mut game.debug_input);
+                game.run_debug_command(command.trim());
             }
+            KeyCode::Esc => game.screen = Screen::Game,
+            KeyCode::Backspace => {
+                game.debug_input.pop();
             }
+            KeyCode::Char(c) => game.debug_input.push(c),
             _ => {}
+        },
+        Screen::DebugDeck => game.screen = Screen::Game,
+        Screen::ConfirmMulligan => {
+            game.screen = Screen::Game;
+            if matches!(key.code, KeyCode::Char('m') | KeyCode::Char('y') | KeyCode::Char('Y')) {
+                game.mulligan();
+            }
+        }
+        Screen::ConfirmSkip => {
+            game.screen = Screen::Game;
+            if matches!(key.code, KeyCode::Char('s') | KeyCode::Char('y') | KeyCode::Char('Y'))
+                && let Err(err) = game.apply_action(Action::Skip)
+            {
+                game.message = err.to_string();
+            }
         }
+        Screen::ConfirmQuit => match key.code {
+            KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Char('y') | KeyCode::Char('Y') => {
+                return true;
+            }
+            _ => {
+                game.screen = Screen::Game;
+            }
+        },
     }
+    false
 }
 
+// Smallest terminal that fits the title, stats, and one row of cards.
+const MIN_WIDTH: u16 = 48;
+const MIN_HEIGHT: u16 = 24;
+
 fn ui(f: &mut Frame, game: &mut GameState) {
     let size = f.area();
+    let theme = game.settings.theme;
+
+    if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+        // Nothing clickable is on screen, so don't let stale areas catch clicks
+        game.card_areas.clear();
+        game.combat_button_areas.clear();
+        render_too_small(f, size);
+        theme.finish(f.buffer_mut());
+        return;
+    }
+
+    let minimap_height = game.settings.minimap as u16;
+    let controls_height = if game.settings.expanded_controls { EXPANDED_CONTROLS_LINES as u16 } else { 1 };
+    let skip_assist = game.skip_assist();
+    let score_assist = game.score_assist();
+    let lethal_warning = game.lethal_warning().filter(|_| !game.show_analysis);
+    let extra_lines = game.equip_hint().is_some() as usize
+        + skip_assist.is_some() as usize
+        + score_assist.is_some() as usize;
+    let info_height = if game.show_analysis {
+        // One line per card plus the tips, inside a border
+        (game.room.len() + 2 + extra_lines) as u16
+    } else if let Some((ref lines, _)) = lethal_warning {
+        (lines.len() + 2 + extra_lines) as u16
+    } else {
+        (1 + extra_lines).max(2) as u16
+    };
 
     // Main layout
     let chunks = Layout::default()
@@ -683,17 +5326,27 @@ fn ui(f: &mut Frame, game: &mut GameState) {
             Constraint::Length(1),  // Slain
             Constraint::Length(1),  // Room label
             Constraint::Min(14),    // Cards (bigger)
-            Constraint::Length(2),  // Card info
-            Constraint::Length(1),  // Controls
+            Constraint::Length(info_height),  // Card info
+            Constraint::Length(minimap_height),  // Mini-map
+            Constraint::Length(controls_height),  // Controls
             Constraint::Length(1),  // Message
         ])
         .split(size);
 
     // Title
-    let title = Paragraph::new("~ SCOUNDREL ~")
+    let ruleset = Line::from(Span::styled(
+        format!(" {} ", game.settings.ruleset()),
+        Style::default().fg(Color::DarkGray),
+    ));
+    let title = Paragraph::new(game.settings.banner.text(theme))
         .style(Style::default().fg(Color::Green).add_modifier(Modifier::BOLD))
         .alignment(Alignment::Center)
-        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded));
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(theme.border(BorderType::Rounded))
+                .title_bottom(ruleset.centered()),
+        );
     f.render_widget(title, chunks[0]);
 
     // Stats row
@@ -713,13 +5366,20 @@ fn ui(f: &mut Frame, game: &mut GameState) {
         Color::Green
     } else if health_pct > 0.25 {
         Color::Yellow
+    } else if game.settings.animations && (game.ticks / 5) % 2 == 1 {
+        // Low HP: pulse between red shades about once a second
+        Color::LightRed
     } else {
         Color::Red
     };
     let bar_width = 10;
     let filled = (health_pct * bar_width as f32) as usize;
-    let bar = format!("{}{}", "█".repeat(filled), "░".repeat(bar_width - filled));
-    let health_text = format!("{}/{}\n{}", game.health, game.max_health, bar);
+    let glyphs = Glyphs::get();
+    let bar = format!("{}{}", glyphs.bar_full.repeat(filled), glyphs.bar_empty.repeat(bar_width - filled));
+    let mut health_text = format!("{}/{}\n{}", game.health, game.max_health, bar);
+    if let Some(delta) = game.ghost_delta() {
+        health_text.push_str(&format!("\n{:+} vs your last run", delta));
+    }
     let health = Paragraph::new(health_text)
         .style(Style::default().fg(health_color))
         .alignment(Alignment::Center)
@@ -727,28 +5387,43 @@ fn ui(f: &mut Frame, game: &mut GameState) {
     f.render_widget(health, stats_chunks[0]);
 
     // Weapon
-    let (weapon_text, weapon_color) = if let Some(ref w) = game.weapon {
-        let durability = if let Some(last) = w.last_monster_slain {
-            if last <= 2 {
-                "Broken".to_string()
-            } else {
-                format!("Hits up to {}", last - 1)
-            }
-        } else {
-            "Full".to_string()
-        };
-        (format!("{}\n{}", w.card.display(), durability), Color::Yellow)
+    let durability = |w: &Weapon| match w.max_hittable(game.settings.durability) {
+        None => "Full".to_string(),
+        Some(max) if max < 2 => "Broken".to_string(),
+        Some(max) => format!("Hits up to {}", max),
+    };
+    let (mut weapon_text, weapon_color) = if let Some(ref w) = game.weapon {
+        (format!("{}\n{}", w.card.display(), durability(w)), Color::Yellow)
     } else {
         ("None".to_string(), Color::DarkGray)
     };
+    if let Some(ref spare) = game.spare {
+        weapon_text.push_str(&format!("\nSpare {}: {}", spare.card.display(), durability(spare)));
+    }
     let weapon = Paragraph::new(weapon_text)
         .style(Style::default().fg(weapon_color))
         .alignment(Alignment::Center)
         .block(Block::default().title(" Weapon ").borders(Borders::ALL).border_style(Style::default().fg(weapon_color)));
     f.render_widget(weapon, stats_chunks[1]);
-
-    // Dungeon
-    let dungeon_text = format!("{}\ncards left", game.dungeon.len());
+    game.weapon_area = stats_chunks[1];
+
+    // Dungeon: a stack that thins as the deck runs down, over the count
+    // (whatever rows the box has spare, 8 cells each, topped up bottom first)
+    const STACK_WIDTH: usize = 8;
+    let stack_rows = stats_chunks[2].height.saturating_sub(3) as usize;
+    let full = game.settings.deck_size().max(1);
+    let mut cells = (game.dungeon.len() * stack_rows * STACK_WIDTH).div_ceil(full);
+    let mut rows = vec![String::new(); stack_rows];
+    for row in rows.iter_mut().rev() {
+        let width = cells.min(STACK_WIDTH);
+        *row = glyphs.stack.repeat(width);
+        cells -= width;
+    }
+    let mut dungeon_text = rows.join("\n");
+    if stack_rows > 0 {
+        dungeon_text.push('\n');
+    }
+    dungeon_text.push_str(&format!("{} cards left", game.dungeon.len()));
     let dungeon = Paragraph::new(dungeon_text)
         .style(Style::default().fg(Color::Blue))
         .alignment(Alignment::Center)
@@ -756,9 +5431,15 @@ fn ui(f: &mut Frame, game: &mut GameState) {
     f.render_widget(dungeon, stats_chunks[2]);
 
     // Turn
-    let remaining = 3 - game.cards_played_this_turn;
-    let pips = format!("{}{}", "● ".repeat(remaining as usize), "○ ".repeat(game.cards_played_this_turn as usize));
-    let potion_status = if game.potion_used_this_turn {
+    let remaining = game.settings.plays_per_room().saturating_sub(game.cards_played_this_turn);
+    let pips = format!(
+        "{}{}",
+        format!("{} ", glyphs.play_left).repeat(remaining as usize),
+        format!("{} ", glyphs.play_used).repeat(game.cards_played_this_turn as usize)
+    );
+    let potion_status = if game.turn_potion.is_some() && game.settings.potions.heals_at_turn_end() {
+        "heal pending"
+    } else if game.turn_potion.is_some() {
         "potion used"
     } else {
         "play cards"
@@ -782,10 +5463,32 @@ fn ui(f: &mut Frame, game: &mut GameState) {
         .alignment(Alignment::Center);
     f.render_widget(slain, chunks[2]);
 
-    // Room label
-    let room_label = Paragraph::new("THE ROOM")
-        .style(Style::default().add_modifier(Modifier::BOLD))
-        .alignment(Alignment::Center);
+    // Room label, tinted by what fighting every monster here would cost:
+    // green under half your HP, yellow under all of it, red at or above
+    let worst_case = game.room_worst_case();
+    let danger = if !game.assist_mode {
+        Color::Reset
+    } else if worst_case >= game.health {
+        Color::Red
+    } else if worst_case * 2 >= game.health {
+        Color::Yellow
+    } else {
+        Color::Green
+    };
+    let room_style = if (game.deadly_flash / 2) % 2 == 1 {
+        Style::default().fg(Color::White).bg(Color::Red)
+    } else {
+        Style::default().fg(danger)
+    };
+    let mut room_spans = vec![Span::styled("THE ROOM", room_style.add_modifier(Modifier::BOLD))];
+    if !game.played_this_turn.is_empty() {
+        let played: Vec<String> = game.played_this_turn.iter().map(|c| c.display()).collect();
+        room_spans.push(Span::styled(
+            format!("  played: {}", played.join(" ")),
+            Style::default().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT),
+        ));
+    }
+    let room_label = Paragraph::new(Line::from(room_spans)).alignment(Alignment::Center);
     f.render_widget(room_label, chunks[3]);
 
     // Cards - 2x2 grid
@@ -797,6 +5500,17 @@ fn ui(f: &mut Frame, game: &mut GameState) {
 
     // Clear and rebuild card areas for mouse clicks
     game.card_areas.clear();
+    let equip_hint = game.equip_hint();
+    let carry = game.carry_candidate();
+    let card_border = game.settings.card_border.border_type();
+    let contents: Vec<String> = (0..game.room.len()).map(|i| card_content(game, i)).collect();
+    // Wide enough for the widest line as the terminal will draw it, so
+    // double-width glyphs can't push the text off center
+    let card_width = contents
+        .iter()
+        .flat_map(|c| c.lines())
+        .map(|line| line.width() as u16 + 4)
+        .fold(CARD_WIDTH, u16::max);
 
     for (row_idx, row_area) in card_rows.iter().enumerate() {
         let cards_in_row: Vec<usize> = (0..game.room.len())
@@ -809,11 +5523,11 @@ fn ui(f: &mut Frame, game: &mut GameState) {
 
         let card_constraints: Vec<Constraint> = cards_in_row
             .iter()
-            .map(|_| Constraint::Length(22))
+            .map(|_| Constraint::Length(card_width))
             .collect();
 
         // Center the cards
-        let total_width: u16 = card_constraints.len() as u16 * 22 + (card_constraints.len() as u16 - 1) * 2;
+        let total_width: u16 = card_constraints.len() as u16 * card_width + (card_constraints.len() as u16 - 1) * 2;
         let padding = (row_area.width.saturating_sub(total_width)) / 2;
 
         let centered_area = Rect {
@@ -837,324 +5551,1057 @@ fn ui(f: &mut Frame, game: &mut GameState) {
                 game.card_areas[card_idx] = card_rects[area_idx];
                 let card = &game.room[card_idx];
                 let is_selected = card_idx == game.selected_index;
+                let (dealt, arriving) = game.deal_state(card_idx);
+                if !dealt {
+                    continue;
+                }
 
                 let (border_color, border_type) = if is_selected {
                     (Color::Cyan, BorderType::Double)
+                } else if equip_hint == Some(card_idx) {
+                    (Color::Yellow, BorderType::Thick)
+                } else if carry == Some(card_idx) {
+                    (Color::Magenta, theme.border(card_border))
+                } else if game.new_room_flash > 0 {
+                    let flash = if game.flawless_room { Color::LightGreen } else { Color::LightYellow };
+                    (flash, theme.border(card_border))
                 } else {
-                    (Color::White, BorderType::Rounded)
+                    (Color::White, theme.border(card_border))
                 };
 
-                // Bigger, clearer card display
-                let rank_display = card.rank_str();
-                let big_rank = if rank_display.len() == 1 {
-                    format!(" {} ", rank_display)
+                let card_color = if game.settings.threat_colors && game.assist_mode && card.is_monster() {
+                    game.threat_color(card)
                 } else {
-                    format!("{} ", rank_display)
+                    card.suit.color()
                 };
-
-                // Show effective damage for monsters when weapon is usable
-                let effect_str = if card.is_monster() && game.can_use_weapon_on(card) {
-                    let wpn = game.weapon.as_ref().unwrap();
-                    let effective_dmg = (card.value() as i32 - wpn.card.value() as i32).max(0);
-                    format!("{}-{}={} dmg", card.value(), wpn.card.value(), effective_dmg)
+                let style = if arriving {
+                    Style::default().fg(Color::DarkGray)
+                } else if is_selected {
+                    Style::default().fg(card_color).add_modifier(Modifier::BOLD)
                 } else {
-                    card.type_str()
+                    Style::default().fg(card_color)
                 };
 
-                let card_content = format!(
-                    "~ {} ~\n\n{}{}\n\n{}\n[{}]",
-                    card.type_label(),
-                    big_rank,
-                    card.suit.symbol(),
-                    effect_str,
-                    card_idx + 1
-                );
-
-                let style = if is_selected {
-                    Style::default().fg(card.suit.color()).add_modifier(Modifier::BOLD)
-                } else {
-                    Style::default().fg(card.suit.color())
-                };
+                let mut card_block = Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(border_type)
+                    .border_style(Style::default().fg(border_color));
+                if carry == Some(card_idx) {
+                    card_block = card_block.title_bottom(Line::from(" carries over ").centered());
+                }
+                let card_widget = Paragraph::new(contents[card_idx].as_str())
+                    .style(style)
+                    .alignment(Alignment::Center)
+                    .block(card_block);
+
+                f.render_widget(card_widget, card_rects[area_idx]);
+            }
+        }
+    }
+
+    // Card info, or every card at once in the analysis panel
+    let equip_tip = equip_hint.map(|idx| {
+        format!(
+            "Tip: equip {} (press {}) before the big monsters arrive.",
+            game.room[idx].display(),
+            idx + 1
+        )
+    });
+    if game.show_analysis {
+        let mut lines: Vec<Line> = game
+            .room
+            .iter()
+            .enumerate()
+            .map(|(idx, card)| {
+                let marker = if idx == game.selected_index { glyphs.cursor } else { " " };
+                Line::from(format!("{} {}", marker, card.describe(idx, game)))
+            })
+            .collect();
+        if let Some(tip) = equip_tip {
+            lines.push(Line::from(tip));
+        }
+        lines.extend(skip_assist.into_iter().chain(score_assist).map(Line::from));
+        let analysis = Paragraph::new(Text::from(lines))
+            .style(Style::default().fg(Color::Cyan))
+            .block(
+                Block::default()
+                    .title(" Room analysis ")
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::DarkGray)),
+            );
+        f.render_widget(analysis, chunks[5]);
+    } else if let Some((lines, critical)) = lethal_warning {
+        // The bare fight kills: a red box instead of a line, filled in when
+        // no fight with the monster can be survived
+        let style = if critical {
+            Style::default().fg(Color::White).bg(Color::Red).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+        };
+        let tips = equip_tip.into_iter().chain(skip_assist).chain(score_assist);
+        let text: Vec<Line> = lines.into_iter().chain(tips).map(Line::from).collect();
+        let warning = Paragraph::new(Text::from(text))
+            .style(style)
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(theme.border(BorderType::Thick))
+                    .border_style(Style::default().fg(Color::Red)),
+            );
+        f.render_widget(warning, chunks[5]);
+    } else {
+        let mut info_lines = Vec::new();
+        if let Some(card) = game.room.get(game.selected_index) {
+            let label = game.card_label(card);
+            let mut spans = vec![Span::raw(format!("{} {} {} ", glyphs.cursor, label, glyphs.separator))];
+            if card.is_monster() {
+                spans.extend(game.monster_outcome(card).into_iter().map(|(text, damage)| match damage {
+                    Some(amount) => Span::styled(text, Style::default().fg(damage_color(amount))),
+                    None => Span::raw(text),
+                }));
+            } else {
+                spans.push(Span::raw(game.card_outcome(card)));
+            }
+            info_lines.push(Line::from(spans));
+        }
+        info_lines.extend(equip_tip.into_iter().chain(skip_assist).chain(score_assist).map(Line::from));
+        let info = Paragraph::new(Text::from(info_lines))
+            .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+            .alignment(Alignment::Center);
+        f.render_widget(info, chunks[5]);
+    }
+
+    if game.settings.minimap {
+        render_minimap(f, game, chunks[6]);
+    }
+
+    // Controls
+    let separator = format!(" {} ", glyphs.separator);
+    let controls_text = if game.settings.expanded_controls {
+        // Grouped so each line fits 80 columns, vim labels included
+        let groups: [(&str, &[&'static str]); EXPANDED_CONTROLS_LINES] = [
+            ("Play", &["Tab/Arrows: move", "Enter/1-4: play", "N/Shift+N: next", "S: skip"]),
+            ("Game", &["A: auto", "U: undo", "P: pause", "K: fewer keys", "Q: quit"]),
+            ("View", &["E: analysis", "I: assists", "T: stats", "W: weapons", "G: monsters"]),
+            ("More", &["L: log", "X: share", "C: theme", "D: dulling", "?: help"]),
+        ];
+        groups
+            .iter()
+            .map(|(group, labels)| {
+                let labels: Vec<&str> = labels.iter().map(|&label| game.settings.keys.label(label)).collect();
+                format!("{}  {}", group, labels.join(&separator))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        [
+            "Tab/Arrows: move",
+            "Enter: play",
+            "S: skip",
+            "E: analysis",
+            "X: share",
+            "T: stats",
+            "W: weapons",
+            "L: log",
+            "?: help",
+            "K: more keys",
+            "Q: quit",
+        ]
+        .map(|label| game.settings.keys.label(label))
+        .join(&separator)
+    };
+    let controls = Paragraph::new(controls_text)
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center);
+    f.render_widget(controls, chunks[7]);
+
+    // Message
+    let msg = if game.screen == Screen::DebugPrompt {
+        Paragraph::new(format!("debug> {}_", game.debug_input)).style(Style::default().fg(Color::Magenta))
+    } else if game.no_safe_moves() {
+        Paragraph::new(format!("{}No safe moves remain - every play is lethal", theme.icon("☠ ")))
+            .style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))
+    } else {
+        Paragraph::new(game.message.as_str()).style(Style::default().fg(Color::Yellow))
+    }
+    .alignment(Alignment::Center);
+    f.render_widget(msg, chunks[8]);
+
+    if let Some(ref popup) = game.damage_popup {
+        render_damage_popup(f, popup);
+    }
+
+    // Modal screens
+    match game.screen {
+        Screen::Combat => render_combat_modal(f, game),
+        Screen::Help => render_help_modal(f, &game.settings),
+        Screen::CardHelp => render_card_help_modal(f, game),
+        Screen::DullingHelp => render_dulling_help_modal(f, game),
+        Screen::Log => render_log_modal(f, game),
+        Screen::GameOver => render_gameover_modal(f, game),
+        Screen::Results => render_results_modal(f, game),
+        Screen::TurnSummary => render_turn_summary(f, game),
+        Screen::ConfirmQuit => render_quit_modal(f, game.settings.theme),
+        Screen::ConfirmSkip => render_skip_modal(f, game),
+        Screen::ConfirmMulligan => render_mulligan_modal(f, game),
+        Screen::DebugDeck => render_debug_deck(f, game),
+        Screen::Export => render_export_modal(f, game),
+        Screen::Stats => render_stats_modal(f, game),
+        Screen::Graveyard => render_graveyard_modal(f, game),
+        Screen::Resume => render_resume_modal(f, game),
+        Screen::Histogram => render_histogram_modal(f, game),
+        Screen::Paused => render_pause_modal(f, game),
+        _ => {}
+    }
+    theme.finish(f.buffer_mut());
+}
+
+/// How a damage figure is colored: white for a scratch, yellow for a real
+/// hit, red for a blow that takes a big bite out of a 20 HP life.
+fn damage_color(amount: i32) -> Color {
+    match amount {
+        ..=3 => Color::White,
+        4..=7 => Color::Yellow,
+        _ => Color::Red,
+    }
+}
+
+/// Narrowest a room card is drawn; `ui` widens all of them if any line needs it.
+const CARD_WIDTH: u16 = 22;
+
+/// The text on a room card's face.
+fn card_content(game: &GameState, index: usize) -> String {
+    let card = &game.room[index];
+    // Bigger, clearer card display; pad by drawn width so one- and
+    // two-column ranks sit on the same center
+    let rank_display = card.rank_str();
+    let big_rank = if rank_display.width() == 1 {
+        format!(" {} ", rank_display)
+    } else {
+        format!("{} ", rank_display)
+    };
+
+    // Show effective damage for monsters when weapon is usable
+    let effect_str = if card.is_monster() && game.can_use_weapon_on(card) {
+        let wpn = game.weapon.as_ref().unwrap();
+        game.settings.combat.preview(card.value(), wpn.card.value())
+    } else {
+        card.type_str()
+    };
+
+    format!(
+        "~ {} ~\n\n{}{}\n\n{}\n[{}]",
+        card.type_label(),
+        big_rank,
+        card.suit.symbol(),
+        effect_str,
+        index + 1
+    )
+}
+
+/// One glyph per room, newest on the right: » for skips, otherwise colored
+/// by how much the room hurt.
+fn render_minimap(f: &mut Frame, game: &GameState, area: Rect) {
+    let label = "Rooms: ";
+    let fits = (area.width as usize).saturating_sub(label.len()) / 2;
+    let shown = &game.rooms[game.rooms.len().saturating_sub(fits)..];
+
+    let glyphs = Glyphs::get();
+    let mut spans = vec![Span::styled(label, Style::default().fg(Color::DarkGray))];
+    for room in shown {
+        let (glyph, color) = if room.skipped {
+            (glyphs.skipped_room, Color::DarkGray)
+        } else if room.damage * 2 >= game.max_health {
+            (glyphs.room, Color::Red)
+        } else if room.damage > room.healed {
+            (glyphs.room, Color::Yellow)
+        } else {
+            (glyphs.room, Color::Green)
+        };
+        spans.push(Span::styled(glyph, Style::default().fg(color)));
+        spans.push(Span::raw(" "));
+    }
+    f.render_widget(Paragraph::new(Line::from(spans)).alignment(Alignment::Center), area);
+}
+
+fn render_combat_modal(f: &mut Frame, game: &mut GameState) {
+    let theme = game.settings.theme;
+    let area = centered_rect(55, 45, f.area());
+    f.render_widget(Clear, area);
+
+    let Some(card) = game.combat_card_index.and_then(|i| game.room.get(i)) else {
+        return;
+    };
+    let options = game.combat_options();
+
+    // Clear button areas
+    game.combat_button_areas.clear();
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            format!("Fighting {} (base damage: {})", game.card_label(card), card.value()),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+    ];
+
+    // A weapon too dull for this monster says so above the buttons
+    let mut btn_y = area.y + 4;
+    for (label, weapon, usable) in [
+        ("Weapon", &game.weapon, game.can_use_weapon_on(card)),
+        ("Spare", &game.spare, game.can_use_spare_on(card)),
+    ] {
+        if let Some(wpn) = weapon.as_ref().filter(|_| !usable) {
+            let max_can_hit = wpn.max_hittable(game.settings.durability).unwrap_or_default();
+            lines.push(Line::from(Span::styled(
+                format!("{} only hits up to {} (monster is {})", label, max_can_hit, card.value()),
+                Style::default().fg(Color::DarkGray),
+            )));
+            btn_y += 1;
+        }
+    }
+    if btn_y > area.y + 4 {
+        lines.push(Line::from(""));
+        btn_y += 1;
+    }
+
+    // The focused option gets the grid's cursor as well as the highlight
+    let selected = Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD);
+    let cursor = Glyphs::get().cursor;
+    let marker = |i: usize| if game.combat_selection == i { cursor } else { " " };
+    // One clickable row per option, Back included; outlook notes sit between
+    let mut button_rows = Vec::new();
+    for (i, action) in options.iter().enumerate() {
+        let (text, damage, color, wielded) = match *action {
+            Action::Fight { use_weapon: true, .. } => {
+                let wpn = game.weapon.as_ref().unwrap();
+                let damage = game.settings.combat.damage(card.value(), wpn.card.value());
+                let text = format!("Use weapon ({}) - take ", wpn.card.display());
+                (text, damage, Color::Green, Some(("weapon", wpn)))
+            }
+            Action::FightWithSpare(_) => {
+                let wpn = game.spare.as_ref().unwrap();
+                let damage = game.settings.combat.damage(card.value(), wpn.card.value());
+                let text = format!("Use spare ({}) - take ", wpn.card.display());
+                (text, damage, Color::Green, Some(("spare", wpn)))
+            }
+            _ => ("Fight barehanded - take ".to_string(), card.value() as i32, Color::Yellow, None),
+        };
+        let style = if game.combat_selection == i { selected } else { Style::default().fg(color) };
+        button_rows.push(lines.len());
+        lines.push(Line::from(vec![
+            Span::styled(format!("{} [{}] {}", marker(i), i + 1, text), style),
+            Span::styled(damage.to_string(), style.fg(damage_color(damage))),
+            Span::styled(" damage", style),
+        ]));
+        if let Some((name, wpn)) = wielded {
+            lines.push(Line::from(Span::styled(
+                format!("    {}", game.weapon_outlook(name, wpn, card)),
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
+    }
+    let back_style = if game.combat_selection == options.len() {
+        selected
+    } else {
+        Style::default().fg(Color::DarkGray)
+    };
+    button_rows.push(lines.len());
+    lines.push(Line::from(Span::styled(format!("{} [B/Esc] Back", marker(options.len())), back_style)));
+
+    let first_button = button_rows[0];
+    for row in button_rows {
+        game.combat_button_areas.push(Rect {
+            x: area.x + 2,
+            y: btn_y + (row - first_button) as u16,
+            width: area.width.saturating_sub(4),
+            height: 1,
+        });
+    }
+
+    // Unwrapped, so each line is one row and the click areas above line up
+    let combat = Paragraph::new(Text::from(lines)).block(
+        Block::default()
+            .title(" Combat ")
+            .borders(Borders::ALL)
+            .border_type(theme.border(BorderType::Double))
+            .border_style(Style::default().fg(Color::Yellow)),
+    );
+    f.render_widget(combat, area);
+}
+
+fn render_help_modal(f: &mut Frame, settings: &Settings) {
+    let theme = settings.theme;
+    let area = centered_rect(70, 80, f.area());
+    f.render_widget(Clear, area);
+
+    let help = Paragraph::new(help_text(settings))
+        .block(
+            Block::default()
+                .title("Help")
+                .borders(Borders::ALL)
+                .border_type(theme.border(BorderType::Double))
+                .border_style(Style::default().fg(Color::Blue)),
+        )
+        .wrap(Wrap { trim: true });
+
+    f.render_widget(help, area);
+}
+
+fn render_card_help_modal(f: &mut Frame, game: &GameState) {
+    let Some(card) = game.room.get(game.selected_index) else {
+        return;
+    };
+    let theme = game.settings.theme;
+    let area = centered_rect(50, 40, f.area());
+    f.render_widget(Clear, area);
+
+    let (title, text) = card_help(card, &game.settings);
+    let lines = vec![
+        Line::from(""),
+        Line::from(text),
+        Line::from(""),
+        Line::from(Span::styled(
+            "? for the full rules · any other key to return",
+            Style::default().fg(Color::DarkGray),
+        )),
+    ];
+    let modal = Paragraph::new(Text::from(lines))
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .title(format!(" {} ", title))
+                .borders(Borders::ALL)
+                .border_type(theme.border(BorderType::Double))
+                .border_style(Style::default().fg(Color::Blue)),
+        );
+    f.render_widget(modal, area);
+}
 
-                let card_widget = Paragraph::new(card_content)
-                    .style(style)
-                    .alignment(Alignment::Center)
-                    .block(
-                        Block::default()
-                            .borders(Borders::ALL)
-                            .border_type(border_type)
-                            .border_style(Style::default().fg(border_color)),
-                    );
+fn render_dulling_help_modal(f: &mut Frame, game: &GameState) {
+    let theme = game.settings.theme;
+    let area = centered_rect(60, 50, f.area());
+    f.render_widget(Clear, area);
 
-                f.render_widget(card_widget, card_rects[area_idx]);
-            }
-        }
+    let mut lines = vec![Line::from(""), Line::from(game.settings.durability.rule())];
+    for paragraph in game.dulling_help() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(paragraph));
     }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "? for the full rules · any other key to return",
+        Style::default().fg(Color::DarkGray),
+    )));
+    let modal = Paragraph::new(Text::from(lines))
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .title(" Weapon dulling ")
+                .borders(Borders::ALL)
+                .border_type(theme.border(BorderType::Double))
+                .border_style(Style::default().fg(Color::Yellow)),
+        );
+    f.render_widget(modal, area);
+}
 
-    // Card info
-    let info_text = if !game.room.is_empty() && game.selected_index < game.room.len() {
-        let card = &game.room[game.selected_index];
-        if card.is_monster() {
-            if game.can_use_weapon_on(card) {
-                let wpn = game.weapon.as_ref().unwrap();
-                let wpn_dmg = (card.value() as i32 - wpn.card.value() as i32).max(0);
-                format!("▶ {} │ {} dmg barehanded, {} with weapon", card.display(), card.value(), wpn_dmg)
-            } else {
-                format!("▶ {} │ {} damage", card.display(), card.value())
-            }
-        } else if card.is_weapon() {
-            format!("▶ {} │ equip for {} attack power", card.display(), card.value())
+/// One paragraph on how a card of this type plays under the session's
+/// rules, for the `?` popup.
+fn card_help(card: &Card, settings: &Settings) -> (&'static str, String) {
+    let dulling = settings.durability.rule();
+    let weapon_damage = match settings.combat {
+        CombatFormula::Classic => "the monster's value minus the weapon's",
+        CombatFormula::Chip => "the monster's value minus the weapon's, but at least 1",
+    };
+    if card.is_monster() {
+        let text = format!(
+            "A monster deals damage equal to its value ({} here). Fight it barehanded and take all of it, \
+             or with your weapon and take {}. {}",
+            card.value(),
+            weapon_damage,
+            dulling
+        );
+        ("Monsters", text)
+    } else if card.is_weapon() {
+        let old = if settings.two_weapons {
+            "moves the one you hold to the spare slot (once both are full, the held one is discarded)"
         } else {
-            let heal = (card.value() as i32).min(game.max_health - game.health);
-            if game.potion_used_this_turn {
-                format!("▶ {} │ wasted - already used potion", card.display())
-            } else {
-                format!("▶ {} │ heal {} HP", card.display(), heal)
+            "discards the one you hold"
+        };
+        let text = format!(
+            "Equipping a weapon {}. Fighting with it, you take {}. {}",
+            old, weapon_damage, dulling
+        );
+        ("Weapons", text)
+    } else {
+        let rule = match settings.potions {
+            PotionRule::OnePerTurn => {
+                "Only one potion heals per turn; a second one in the same turn is wasted."
             }
+            PotionRule::Forgiving => "Every potion heals, even several in one turn.",
+            PotionRule::Best => {
+                "Only the largest potion of a turn heals, once the turn is over: a bigger one drunk \
+                 after a smaller takes its place, and one no bigger is wasted."
+            }
+        };
+        let text = format!(
+            "A potion restores HP equal to its value, up to {} HP. {}",
+            settings.max_health, rule
+        );
+        ("Potions", text)
+    }
+}
+
+/// The rules as configured for this session, so the help never describes a
+/// rule that isn't in effect.
+fn help_text(settings: &Settings) -> String {
+    let dulling = match settings.durability {
+        Durability::Strict => {
+            "  • Weapon dulling: After killing a monster, weapon\n    can only hit monsters with LOWER value (not equal)"
         }
+        Durability::Equal => {
+            "  • Weapon dulling: After killing a monster, weapon\n    can hit equal-value or lower monsters"
+        }
+        Durability::Off => "  • Weapons never dull: they can hit any monster",
+    };
+    let rooms = if settings.carry {
+        "  • A room has 4 cards - you must play exactly 3\n  • The 4th card stays for the next room"
     } else {
-        String::new()
+        "  • A room has 4 cards - you play all of them\n  • Every room is a fresh deal of 4"
     };
-    let info = Paragraph::new(info_text)
-        .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
-        .alignment(Alignment::Center);
-    f.render_widget(info, chunks[5]);
+    let weapon_damage = match settings.combat {
+        CombatFormula::Classic => "  • Use weapon: take (monster - weapon) damage",
+        CombatFormula::Chip => "  • Use weapon: take (monster - weapon) damage, at least 1",
+    };
+    let spare = if settings.two_weapons {
+        "\n  • Two weapon slots: a new weapon keeps the old one\n    as a spare (once full, the held one is discarded);\n    each fight picks which to use"
+    } else {
+        ""
+    };
+    let skipping = match settings.skip_penalty {
+        SkipPenalty::Off => "  • You may skip a room (but not twice in a row)",
+        SkipPenalty::Health => "  • You may skip a room for 1 HP (not twice in a row)",
+        SkipPenalty::Score => "  • You may skip a room for 1 point (not twice in a row)",
+    };
+    let skip_order = match settings.skip_order {
+        SkipOrder::Bottom => "  • Skipped cards go to the bottom of the dungeon",
+        SkipOrder::Shuffle => "  • Skipped cards are shuffled back into the dungeon",
+        SkipOrder::Front => "  • Skipped cards come back right after the next room",
+    };
+    let potions = match settings.potions {
+        PotionRule::OnePerTurn => "  • Only ONE potion per turn (second is wasted)",
+        PotionRule::Forgiving => "  • Every potion heals, even several in one turn",
+        PotionRule::Best => "  • Only the largest potion in a turn heals, at the end of the turn",
+    };
+    let vim = match settings.keys {
+        KeyMap::Standard => "",
+        KeyMap::Vim => "\n  h/l, j/k      Navigate cards (--keys vim: L and K are Shift+L, Shift+K)",
+    };
+    let glyphs = Glyphs::get();
+    let suits = glyphs.suits;
 
-    // Controls
-    let controls_text = "Tab/Arrows: move │ Enter: play │ S: skip │ L: log │ ?: help │ Q: quit";
-    let controls = Paragraph::new(controls_text)
-        .style(Style::default().fg(Color::DarkGray))
-        .alignment(Alignment::Center);
-    f.render_widget(controls, chunks[6]);
+    format!(
+        r#"SCOUNDREL RULES
+By Zach Gage and Kurt Bieg (2011)
+{ruleset}
 
-    // Message
-    let msg = Paragraph::new(game.message.as_str())
-        .style(Style::default().fg(Color::Yellow))
-        .alignment(Alignment::Center);
-    f.render_widget(msg, chunks[7]);
+GOAL
+Survive the dungeon by playing through all {deck} cards.
 
-    // Modal screens
-    match game.screen {
-        Screen::Combat => render_combat_modal(f, game),
-        Screen::Help => render_help_modal(f),
-        Screen::Log => render_log_modal(f, game),
-        Screen::GameOver => render_gameover_modal(f, game),
-        Screen::ConfirmQuit => render_quit_modal(f),
-        _ => {}
-    }
+CARD TYPES
+  {spades} {clubs} Monsters  Deal damage equal to their value (2-14)
+  {diamonds} Weapons     Reduce monster damage by weapon value (2-{red})
+  {hearts} Potions     Restore health (2-{red}, max {hp} HP)
+
+EACH TURN
+{rooms}
+{skipping}
+{skip_order}
+
+COMBAT
+  • Fight barehanded: take full monster damage
+{weapon_damage}
+{dulling}{spare}
+
+POTIONS
+{potions}
+  • Cannot heal above {hp} HP
+
+CONTROLS
+  Tab/Arrows    Navigate cards{vim}
+  N / Shift+N   Jump to the next monster / next play that won't kill you
+  Enter/Space   Play selected card
+  S             Skip room (asks first in the last two rooms)
+  E             Toggle room analysis
+  I             Hide or show all assists (threat colors, hints, --assist lines)
+  X             Export dungeon as a puzzle code
+  T             Run stats
+  W             Weapon graveyard
+  G             Monsters left, by rank
+  A             Auto-play the only safe move (--auto-advance)
+  U             Take back the last card in this room (--safe)
+  M             Redeal a hopeless first room, once (--mulligan)
+  P             Pause (stops the clock; P again resumes)
+  K             Show every key at the bottom, or just the main ones
+  C             Try the next theme (standard or minimal) for this run
+  D             How weapon dulling works, for the weapon you hold
+  L             View log (1/2/3 filter: combat, potions, all)
+  ?             The rules for the selected card (? again: this help)
+  Q             Quit (Shift+Q skips the confirmation)
+
+Press any key to close"#,
+        ruleset = settings.ruleset(),
+        vim = vim,
+        deck = settings.deck_size(),
+        red = settings.red_max_rank,
+        hp = settings.max_health,
+        spades = suits[0],
+        clubs = suits[1],
+        hearts = suits[2],
+        diamonds = suits[3],
+    )
+    // The rule lines above are written with Unicode bullets
+    .replace('•', glyphs.bullet)
 }
 
-fn render_combat_modal(f: &mut Frame, game: &mut GameState) {
-    let area = centered_rect(55, 45, f.area());
+fn render_log_modal(f: &mut Frame, game: &GameState) {
+    let theme = game.settings.theme;
+    let area = centered_rect(70, 80, f.area());
     f.render_widget(Clear, area);
 
-    let card_idx = game.combat_card_index.unwrap();
-    let card = &game.room[card_idx];
-    let can_use_weapon = game.can_use_weapon_on(card);
+    let shown: Vec<&LogEntry> = game
+        .log
+        .iter()
+        .filter(|entry| game.log_filter.matches(entry.kind()))
+        .collect();
+    let log_entries: Vec<Line> = shown[shown.len().saturating_sub(20)..]
+        .iter()
+        .map(|entry| Line::from(entry.text()))
+        .collect();
 
-    // Clear button areas
-    game.combat_button_areas.clear();
+    let mut lines = vec![Line::from(Span::styled(
+        format!("{}ADVENTURE LOG", theme.icon("📜 ")),
+        Style::default().add_modifier(Modifier::BOLD),
+    ))];
+    lines.push(Line::from(""));
+    // Scrolled back as far as memory goes: say the start isn't the run's start
+    if game.log_dropped > 0 && shown.len() <= 20 {
+        lines.push(Line::from(Span::styled(
+            format!("… {} older entries dropped (--log-limit)", game.log_dropped),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    lines.extend(log_entries);
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "1: combat · 2: potions · 3: all · any other key to close",
+        Style::default().fg(Color::DarkGray),
+    )));
 
-    // Calculate button positions within the modal
-    let inner_area = Rect {
-        x: area.x + 2,
-        y: area.y + 4,
-        width: area.width - 4,
-        height: 3,
-    };
+    let log = Paragraph::new(Text::from(lines)).block(
+        Block::default()
+            .title(format!("Log ({})", game.log_filter.name()))
+            .borders(Borders::ALL)
+            .border_type(theme.border(BorderType::Double))
+            .border_style(Style::default().fg(Color::Blue)),
+    );
+
+    f.render_widget(log, area);
+}
+
+fn render_stats_modal(f: &mut Frame, game: &GameState) {
+    let theme = game.settings.theme;
+    let area = centered_rect(60, 40, f.area());
+    f.render_widget(Clear, area);
 
     let mut lines = vec![
         Line::from(Span::styled(
-            format!("Fighting {} (base damage: {})", card.display(), card.value()),
+            "RUN STATS",
             Style::default().add_modifier(Modifier::BOLD),
         )),
         Line::from(""),
     ];
+    lines.extend(game.tally_lines().into_iter().map(Line::from));
+    lines.push(Line::from(format!("Moves: {}", game.moves)));
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Press any key to close",
+        Style::default().fg(Color::DarkGray),
+    )));
 
-    if can_use_weapon {
-        let wpn = game.weapon.as_ref().unwrap();
-        let wpn_dmg = (card.value() as i32 - wpn.card.value() as i32).max(0);
+    let stats = Paragraph::new(Text::from(lines))
+        .alignment(Alignment::Center)
+        .block(
+            Block::default()
+                .title("Stats")
+                .borders(Borders::ALL)
+                .border_type(theme.border(BorderType::Double))
+                .border_style(Style::default().fg(Color::Blue)),
+        );
 
-        let style_0 = if game.combat_selection == 0 {
-            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
-        } else {
-            Style::default().fg(Color::Green)
-        };
-        let style_1 = if game.combat_selection == 1 {
-            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
-        } else {
-            Style::default().fg(Color::Yellow)
-        };
-        let style_2 = if game.combat_selection == 2 {
-            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
-        } else {
-            Style::default().fg(Color::DarkGray)
-        };
+    f.render_widget(stats, area);
+}
+
+fn render_graveyard_modal(f: &mut Frame, game: &GameState) {
+    let theme = game.settings.theme;
+    let area = centered_rect(50, 50, f.area());
+    f.render_widget(Clear, area);
 
+    let kills = |n: usize| if n == 1 { "1 kill".to_string() } else { format!("{} kills", n) };
+    let mut lines = vec![
+        Line::from(Span::styled(
+            format!("{}WEAPON GRAVEYARD", theme.icon("⚰ ")),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+    ];
+    if game.weapon_history.is_empty() {
         lines.push(Line::from(Span::styled(
-            format!("[1] Use weapon ({}) - take {} damage", wpn.card.display(), wpn_dmg),
-            style_0,
+            "No weapons retired yet",
+            Style::default().fg(Color::DarkGray),
         )));
+    }
+    for (card, slain) in &game.weapon_history {
+        lines.push(Line::from(format!("{}  {}", card.display(), kills(*slain))));
+    }
+    if let Some(ref weapon) = game.weapon {
         lines.push(Line::from(Span::styled(
-            format!("[2] Fight barehanded - take {} damage", card.value()),
-            style_1,
+            format!("{}  {} (in hand)", weapon.card.display(), kills(game.monsters_on_weapon.len())),
+            Style::default().fg(Color::Cyan),
         )));
-        lines.push(Line::from(Span::styled("[B/Esc] Back", style_2)));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Press any key to close",
+        Style::default().fg(Color::DarkGray),
+    )));
 
-        // Store button areas (3 buttons)
-        game.combat_button_areas.push(Rect { x: inner_area.x, y: inner_area.y, width: inner_area.width, height: 1 });
-        game.combat_button_areas.push(Rect { x: inner_area.x, y: inner_area.y + 1, width: inner_area.width, height: 1 });
-        game.combat_button_areas.push(Rect { x: inner_area.x, y: inner_area.y + 2, width: inner_area.width, height: 1 });
-    } else {
-        if game.weapon.is_some() {
-            let wpn = game.weapon.as_ref().unwrap();
-            let max_can_hit = wpn.last_monster_slain.unwrap() - 1;
-            lines.push(Line::from(Span::styled(
-                format!("Weapon only hits up to {} (monster is {})", max_can_hit, card.value()),
-                Style::default().fg(Color::DarkGray),
-            )));
-            lines.push(Line::from(""));
-        }
+    let graveyard = Paragraph::new(Text::from(lines))
+        .alignment(Alignment::Center)
+        .block(
+            Block::default()
+                .title("Weapons")
+                .borders(Borders::ALL)
+                .border_type(theme.border(BorderType::Double))
+                .border_style(Style::default().fg(Color::Blue)),
+        );
 
-        let style_0 = if game.combat_selection == 0 {
-            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
-        } else {
-            Style::default().fg(Color::Yellow)
-        };
-        let style_1 = if game.combat_selection == 1 {
-            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
-        } else {
-            Style::default().fg(Color::DarkGray)
-        };
+    f.render_widget(graveyard, area);
+}
 
-        lines.push(Line::from(Span::styled(
-            format!("[1] Fight barehanded - take {} damage", card.value()),
-            style_0,
-        )));
-        lines.push(Line::from(Span::styled("[B/Esc] Back", style_1)));
+/// Monsters still to come, one column per rank, a block per copy left.
+fn render_histogram_modal(f: &mut Frame, game: &GameState) {
+    let theme = game.settings.theme;
+    let area = centered_rect(60, 40, f.area());
+    f.render_widget(Clear, area);
 
-        // Store button areas (2 buttons)
-        let btn_y = if game.weapon.is_some() { inner_area.y + 2 } else { inner_area.y };
-        game.combat_button_areas.push(Rect { x: inner_area.x, y: btn_y, width: inner_area.width, height: 1 });
-        game.combat_button_areas.push(Rect { x: inner_area.x, y: btn_y + 1, width: inner_area.width, height: 1 });
+    let counts = game.monsters_left_by_rank();
+    let tallest = counts.iter().copied().max().unwrap_or(0);
+    let column = |text: &str| format!("{:^3}", text);
+    let mut lines = vec![
+        Line::from(Span::styled(
+            "MONSTERS LEFT BY RANK",
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+    ];
+    for level in (1..=tallest).rev() {
+        let row: String = counts
+            .iter()
+            .map(|&n| column(if n >= level { Glyphs::get().bar_full } else { " " }))
+            .collect();
+        lines.push(Line::from(Span::styled(row, Style::default().fg(Color::Red))));
     }
+    let ranks: String = (2..=14)
+        .map(|rank| column(&Card { suit: Suit::Spades, rank }.rank_str()))
+        .collect();
+    lines.push(Line::from(ranks));
+    let total: usize = counts.iter().sum();
+    lines.push(Line::from(""));
+    lines.push(Line::from(format!("{} monsters left", total)));
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Press any key to close",
+        Style::default().fg(Color::DarkGray),
+    )));
 
-    let combat = Paragraph::new(Text::from(lines))
+    let histogram = Paragraph::new(Text::from(lines))
+        .alignment(Alignment::Center)
         .block(
             Block::default()
-                .title(" Combat ")
+                .title("Remaining")
                 .borders(Borders::ALL)
-                .border_type(BorderType::Double)
-                .border_style(Style::default().fg(Color::Yellow)),
-        )
-        .wrap(Wrap { trim: true });
+                .border_type(theme.border(BorderType::Double))
+                .border_style(Style::default().fg(Color::Blue)),
+        );
 
-    f.render_widget(combat, area);
+    f.render_widget(histogram, area);
 }
 
-fn render_help_modal(f: &mut Frame) {
-    let area = centered_rect(70, 80, f.area());
+/// `--debug`: the dungeon from the next card dealt to the last.
+fn render_debug_deck(f: &mut Frame, game: &GameState) {
+    let theme = game.settings.theme;
+    let area = centered_rect(70, 50, f.area());
     f.render_widget(Clear, area);
 
-    let help_text = r#"SCOUNDREL RULES
-By Zach Gage and Kurt Bieg (2011)
-
-GOAL
-Survive the dungeon by playing through all 44 cards.
+    let order: Vec<String> = game.dungeon.iter().map(|c| c.display()).collect();
+    let lines = vec![
+        Line::from(Span::styled(
+            format!("DUNGEON ORDER ({} cards, next first)", order.len()),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(order.join(" ")),
+        Line::from(""),
+        Line::from(format!("State hash {:016x}", game.state_hash())),
+        Line::from(""),
+        Line::from(Span::styled("Press any key to close", Style::default().fg(Color::DarkGray))),
+    ];
+    let deck = Paragraph::new(Text::from(lines))
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .title(" Debug ")
+                .borders(Borders::ALL)
+                .border_type(theme.border(BorderType::Double))
+                .border_style(Style::default().fg(Color::Magenta)),
+        );
+    f.render_widget(deck, area);
+}
 
-CARD TYPES
-  ♠ ♣ Monsters  Deal damage equal to their value (2-14)
-  ♦ Weapons     Reduce monster damage by weapon value
-  ♥ Potions     Restore health (max 20 HP)
+fn render_export_modal(f: &mut Frame, game: &GameState) {
+    let theme = game.settings.theme;
+    let area = centered_rect(70, 40, f.area());
+    f.render_widget(Clear, area);
 
-EACH TURN
-  • A room has 4 cards - you must play exactly 3
-  • The 4th card stays for the next room
-  • You may skip a room (but not twice in a row)
+    let lines = vec![
+        Line::from(Span::styled(
+            "SHARE THIS DUNGEON",
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from("Anyone can play this exact card order with:"),
+        Line::from(""),
+        Line::from(Span::styled(
+            format!("scoundrel --puzzle {}", encode_puzzle(&game.initial_order)),
+            Style::default().fg(Color::Cyan),
+        )),
+        Line::from(""),
+        Line::from(format!("Difficulty: {}", game.difficulty_rating())),
+        Line::from(""),
+        Line::from(Span::styled(
+            "Press any key to close",
+            Style::default().fg(Color::DarkGray),
+        )),
+    ];
 
-COMBAT
-  • Fight barehanded: take full monster damage
-  • Use weapon: take (monster - weapon) damage
-  • Weapon dulling: After killing a monster, weapon
-    can only hit monsters with LOWER value (not equal)
+    let export = Paragraph::new(Text::from(lines))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .title("Export")
+                .borders(Borders::ALL)
+                .border_type(theme.border(BorderType::Double))
+                .border_style(Style::default().fg(Color::Blue)),
+        );
 
-POTIONS
-  • Only ONE potion per turn (second is wasted)
-  • Cannot heal above 20 HP
+    f.render_widget(export, area);
+}
 
-CONTROLS
-  Tab/Arrows    Navigate cards
-  Enter/Space   Play selected card
-  S             Skip room
-  L             View log
-  ?             This help
-  Q             Quit
+fn render_turn_summary(f: &mut Frame, game: &GameState) {
+    let theme = game.settings.theme;
+    let Some(room) = game.rooms.last() else {
+        return;
+    };
+    let area = centered_rect(40, 25, f.area());
+    f.render_widget(Clear, area);
 
-Press any key to close"#;
+    let slain = match room.slain {
+        1 => "1 monster".to_string(),
+        n => format!("{} monsters", n),
+    };
+    let lines = vec![
+        Line::from(Span::styled(
+            format!("TURN {} DONE", game.turn_number - 1),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(Span::styled(
+            format!("Took {} damage", room.damage),
+            Style::default().fg(Color::Red),
+        )),
+        Line::from(Span::styled(
+            format!("Healed {} HP", room.healed),
+            Style::default().fg(Color::Green),
+        )),
+        Line::from(format!("Slew {}", slain)),
+        Line::from(format!("{} HP left", game.health)),
+        Line::from(""),
+        Line::from(Span::styled(
+            "Press any key to continue",
+            Style::default().fg(Color::DarkGray),
+        )),
+    ];
 
-    let help = Paragraph::new(help_text)
+    let summary = Paragraph::new(Text::from(lines))
+        .alignment(Alignment::Center)
         .block(
             Block::default()
-                .title("Help")
                 .borders(Borders::ALL)
-                .border_type(BorderType::Double)
-                .border_style(Style::default().fg(Color::Blue)),
-        )
-        .wrap(Wrap { trim: true });
+                .border_type(theme.border(BorderType::Double))
+                .border_style(Style::default().fg(Color::Magenta)),
+        );
 
-    f.render_widget(help, area);
+    f.render_widget(summary, area);
 }
 
-fn render_log_modal(f: &mut Frame, game: &GameState) {
-    let area = centered_rect(70, 80, f.area());
-    f.render_widget(Clear, area);
-
-    let log_entries: Vec<Line> = game
-        .log
+/// Damage taken in each room as a bar chart, colored like the damage
+/// numbers, with skipped rooms marked on an empty bar. Also gives the
+/// width the bars take up, for centering.
+fn room_damage_chart(game: &GameState, width: u16) -> (BarChart<'static>, u16) {
+    let glyphs = Glyphs::get();
+    let bars: Vec<Bar> = game
+        .rooms
         .iter()
-        .rev()
-        .take(20)
-        .rev()
-        .map(|s| Line::from(s.as_str()))
+        .enumerate()
+        .map(|(n, room)| {
+            let bar = Bar::default()
+                .value(room.damage.max(0) as u64)
+                .label(Line::from((n + 1).to_string()));
+            if room.skipped {
+                bar.text_value(glyphs.skipped_room.to_string()).style(Style::default().fg(Color::DarkGray))
+            } else {
+                bar.style(Style::default().fg(damage_color(room.damage)))
+            }
+        })
         .collect();
+    // As wide as fits, up to 5 columns, with a gap between bars while there's room
+    let count = bars.len().max(1) as u16;
+    let gap = u16::from(width >= count * 2);
+    let bar_width = ((width + gap) / count).saturating_sub(gap).clamp(1, 5);
+    let most = game.rooms.iter().map(|r| r.damage).max().unwrap_or(0).max(1);
+    let used = (count * (bar_width + gap)).saturating_sub(gap).min(width);
+    let chart = BarChart::default()
+        .block(Block::default().title(" Damage per room ").title_alignment(Alignment::Center))
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(bar_width)
+        .bar_gap(gap)
+        .bar_set(glyphs.bar_set())
+        .max(most as u64);
+    (chart, used)
+}
 
-    let mut lines = vec![Line::from(Span::styled(
-        "📜 ADVENTURE LOG",
-        Style::default().add_modifier(Modifier::BOLD),
-    ))];
+/// Where every card ended up, suit by suit, and how each room went.
+fn render_results_modal(f: &mut Frame, game: &mut GameState) {
+    let theme = game.settings.theme;
+    let area = centered_rect(60, 80, f.area());
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .title("Results")
+        .title_bottom(Line::from(" ↑/↓: scroll · Enter: continue ").centered())
+        .borders(Borders::ALL)
+        .border_type(theme.border(BorderType::Double))
+        .border_style(Style::default().fg(Color::Green));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    // The chart stays put above the scrolling text, given the room for both
+    let chart_height = if !game.rooms.is_empty() && inner.height >= 20 { 8 } else { 0 };
+    let [chart_area, text_area] =
+        Layout::vertical([Constraint::Length(chart_height), Constraint::Min(0)]).areas(inner);
+    if chart_height > 0 {
+        let (chart, used) = room_damage_chart(game, chart_area.width);
+        let margin = (chart_area.width - used) / 2;
+        f.render_widget(chart, Rect { x: chart_area.x + margin, width: used, ..chart_area });
+    }
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            "RESULTS",
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+    ];
+    lines.extend(game.tally_lines().into_iter().map(Line::from));
+    lines.extend(game.weapon_efficiency_line().map(Line::from));
     lines.push(Line::from(""));
-    lines.extend(log_entries);
+    let chart = game.hp_chart_lines();
+    if !chart.is_empty() {
+        lines.extend(chart);
+        lines.push(Line::from(""));
+    }
+
+    let held = game.weapon.as_ref().map(|w| w.card);
+    let played: Vec<Card> = game
+        .discard
+        .iter()
+        .chain(game.monsters_on_weapon.iter())
+        .chain(held.iter())
+        .copied()
+        .collect();
+    for suit in SUITS {
+        let cards: Vec<&Card> = played.iter().filter(|c| c.suit == suit).collect();
+        let Some(first) = cards.first() else {
+            continue;
+        };
+        let total: u32 = cards.iter().map(|c| c.value() as u32).sum();
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{} {:<8}", suit.symbol(), first.type_label()),
+                Style::default().fg(suit.color()),
+            ),
+            Span::raw(format!("{:>2} cards, {:>3} total", cards.len(), total)),
+        ]));
+    }
     lines.push(Line::from(""));
-    lines.push(Line::from(Span::styled(
-        "Press any key to close",
-        Style::default().fg(Color::DarkGray),
-    )));
 
-    let log = Paragraph::new(Text::from(lines)).block(
-        Block::default()
-            .title("Log")
-            .borders(Borders::ALL)
-            .border_type(BorderType::Double)
-            .border_style(Style::default().fg(Color::Blue)),
-    );
+    for (n, room) in game.rooms.iter().enumerate() {
+        let text = if room.skipped {
+            format!("Room {:>2}: skipped", n + 1)
+        } else {
+            format!("Room {:>2}: took {:>2}, healed {:>2}", n + 1, room.damage, room.healed)
+        };
+        lines.push(Line::from(text));
+    }
 
-    f.render_widget(log, area);
+    // No further than the last line reaching the bottom, so Up answers at once
+    let max_scroll = (lines.len() as u16).saturating_sub(text_area.height);
+    game.results_scroll = game.results_scroll.min(max_scroll);
+    let results = Paragraph::new(Text::from(lines))
+        .alignment(Alignment::Center)
+        .scroll((game.results_scroll, 0));
+
+    f.render_widget(results, text_area);
 }
 
 fn render_gameover_modal(f: &mut Frame, game: &GameState) {
+    let theme = game.settings.theme;
     if game.won {
         // Victory screen
-        let area = centered_rect(60, 50, f.area());
+        let area = centered_rect(60, if game.settings.leaderboard.is_some() { 80 } else { 50 }, f.area());
         f.render_widget(Clear, area);
+        let area = render_leaderboard_box(f, game, area);
 
-        let victory_art = r#"
+        let victory_art = Glyphs::get().art(
+            r#"
     ██╗   ██╗██╗ ██████╗████████╗ ██████╗ ██████╗ ██╗   ██╗
     ██║   ██║██║██╔════╝╚══██╔══╝██╔═══██╗██╔══██╗╚██╗ ██╔╝
     ██║   ██║██║██║        ██║   ██║   ██║██████╔╝ ╚████╔╝
     ╚██╗ ██╔╝██║██║        ██║   ██║   ██║██╔══██╗  ╚██╔╝
      ╚████╔╝ ██║╚██████╗   ██║   ╚██████╔╝██║  ██║   ██║
       ╚═══╝  ╚═╝ ╚═════╝   ╚═╝    ╚═════╝ ╚═╝  ╚═╝   ╚═╝
-"#;
+"#,
+            r#"
+ __     ___      _
+ \ \   / (_) ___| |_ ___  _ __ _   _
+  \ \ / /| |/ __| __/ _ \| '__| | | |
+   \ V / | | (__| || (_) | |  | |_| |
+    \_/  |_|\___|\__\___/|_|   \__, |
+                               |___/
+"#,
+        );
 
         let mut lines: Vec<Line> = victory_art
             .lines()
@@ -1163,31 +6610,46 @@ fn render_gameover_modal(f: &mut Frame, game: &GameState) {
 
         lines.push(Line::from(""));
         lines.push(Line::from(Span::styled(
-            "🏆 You conquered the dungeon! 🏆",
+            format!("{}You conquered the dungeon!{}", theme.icon("🏆 "), theme.icon(" 🏆")),
             Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
         )));
         lines.push(Line::from(""));
-        lines.push(Line::from(format!("Final Score: {}", game.calculate_score())));
+        lines.push(Line::from(game.score_line()));
         lines.push(Line::from(format!("HP Remaining: {}", game.health)));
+        lines.push(Line::from(format!("Moves: {}", game.moves)));
+        lines.push(Line::from(format!("Time: {}", game.clock())));
+        lines.extend(game.tally_lines().into_iter().map(Line::from));
+        lines.extend(game.weapon_efficiency_line().map(Line::from));
+        if game.avoidable_mistakes == 0 {
+            lines.push(Line::from(Span::styled(
+                format!("{}Perfect run!{}", theme.icon("✨ "), theme.icon(" ✨")),
+                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            )));
+        } else {
+            lines.push(Line::from(format!("Avoidable mistakes: {}", game.avoidable_mistakes)));
+        }
+        lines.push(Line::from(game.seed_line()));
         lines.push(Line::from(""));
-        lines.push(Line::from("Play again? [Y/n]"));
+        lines.push(Line::from(game.game_over_prompt()));
 
         let gameover = Paragraph::new(Text::from(lines))
             .alignment(Alignment::Center)
             .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .border_type(BorderType::Double)
+                    .border_type(theme.border(BorderType::Double))
                     .border_style(Style::default().fg(Color::Green)),
             );
 
         f.render_widget(gameover, area);
     } else {
         // Death screen - medieval style
-        let area = centered_rect(70, 60, f.area());
+        let area = centered_rect(70, if game.settings.leaderboard.is_some() { 90 } else { 60 }, f.area());
         f.render_widget(Clear, area);
+        let area = render_leaderboard_box(f, game, area);
 
-        let death_art = r#"
+        let death_art = Glyphs::get().art(
+            r#"
    ▄██   ▄    ▄██████▄  ▄█   ▄█       ████████▄   ▄█     ▄████████ ████████▄
    ███   ██▄ ███    ███ ███  ███      ███   ▀███ ███    ███    ███ ███   ▀███
    ███▄▄▄███ ███    ███ ███  ███      ███    ███ ███▌   ███    █▀  ███    ███
@@ -1204,7 +6666,15 @@ fn render_gameover_modal(f: &mut Frame, game: &GameState) {
                            ░░░░█░░░░░░░░░░░░█░░░░░
                            ░░░░░▀▄░░▀▀▀░░░▄▀░░░░░░
                             ░░░░░░░▀▀▀▀▀▀▀░░░░░░░
-"#;
+"#,
+            r#"
+ __   _____  _   _   ____ ___ _____ ____
+ \ \ / / _ \| | | | |  _ \_ _| ____|  _ \
+  \ V / | | | | | | | | | | ||  _| | | | |
+   | || |_| | |_| | | |_| | || |___| |_| |
+   |_| \___/ \___/  |____/___|_____|____/
+"#,
+        );
 
         let mut lines: Vec<Line> = death_art
             .lines()
@@ -1216,11 +6686,28 @@ fn render_gameover_modal(f: &mut Frame, game: &GameState) {
             "The dungeon has claimed another soul...",
             Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
         )));
+        if let Some((card, with_weapon, damage)) = game.killing_blow {
+            lines.push(Line::from(Span::styled(
+                format!(
+                    "Died to {} fought {} for {}",
+                    card.display(),
+                    if with_weapon { "with your weapon" } else { "barehanded" },
+                    damage
+                ),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            )));
+        }
         lines.push(Line::from(""));
-        lines.push(Line::from(format!("Final Score: {}", game.calculate_score())));
+        lines.push(Line::from(game.score_line()));
+        lines.push(Line::from(format!("Moves: {}", game.moves)));
+        lines.push(Line::from(format!("Time: {}", game.clock())));
+        lines.extend(game.tally_lines().into_iter().map(Line::from));
+        lines.extend(game.weapon_efficiency_line().map(Line::from));
+        lines.extend(game.hp_chart_lines());
+        lines.push(Line::from(game.seed_line()));
         lines.push(Line::from(""));
         lines.push(Line::from(Span::styled(
-            "Play again? [Y/n]",
+            game.game_over_prompt(),
             Style::default().fg(Color::White),
         )));
 
@@ -1229,7 +6716,7 @@ fn render_gameover_modal(f: &mut Frame, game: &GameState) {
             .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .border_type(BorderType::Double)
+                    .border_type(theme.border(BorderType::Double))
                     .border_style(Style::default().fg(Color::Red)),
             );
 
@@ -1237,11 +6724,182 @@ fn render_gameover_modal(f: &mut Frame, game: &GameState) {
     }
 }
 
-fn render_quit_modal(f: &mut Frame) {
+/// With `--leaderboard`, box the standings along the bottom of the
+/// game-over modal's `area`, and return what's left above them.
+fn render_leaderboard_box(f: &mut Frame, game: &GameState, area: Rect) -> Rect {
+    if game.settings.leaderboard.is_none() {
+        return area;
+    }
+    let lines = game.leaderboard_lines();
+    let height = (lines.len() as u16 + 2).min(area.height / 2);
+    let [rest, board] = Layout::vertical([Constraint::Min(0), Constraint::Length(height)]).areas(area);
+    let block = Block::default()
+        .title(" Leaderboard ")
+        .borders(Borders::ALL)
+        .border_type(game.settings.theme.border(BorderType::Rounded))
+        .border_style(Style::default().fg(Color::Yellow));
+    f.render_widget(Paragraph::new(Text::from(lines)).alignment(Alignment::Center).block(block), board);
+    rest
+}
+
+fn render_resume_modal(f: &mut Frame, game: &GameState) {
+    let theme = game.settings.theme;
+    let Some(ref checkpoint) = game.pending_resume else {
+        return;
+    };
+    let area = centered_rect(50, 30, f.area());
+    f.render_widget(Clear, area);
+
+    let lines = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            "Unfinished run found",
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(format!("Turn {} · seed {}", checkpoint.turn, checkpoint.seed)),
+        Line::from(checkpoint.summary.as_ref().map_or(String::new(), SaveSummary::describe)),
+        Line::from(""),
+        Line::from("Resume it? [Y/n]"),
+        Line::from(Span::styled(
+            "n starts the fresh deal and forgets the old run",
+            Style::default().fg(Color::DarkGray),
+        )),
+    ];
+    let modal = Paragraph::new(Text::from(lines))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .title(" Checkpoint ")
+                .borders(Borders::ALL)
+                .border_type(theme.border(BorderType::Double))
+                .border_style(Style::default().fg(Color::Yellow)),
+        );
+    f.render_widget(modal, area);
+}
+
+fn render_pause_modal(f: &mut Frame, game: &GameState) {
+    let theme = game.settings.theme;
+    let area = centered_rect(40, 25, f.area());
+    f.render_widget(Clear, area);
+
+    let lines = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            "Paused.",
+            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(format!("Clock stopped at {}", game.clock())),
+        Line::from(""),
+        Line::from(Span::styled("Press P to resume", Style::default().fg(Color::DarkGray))),
+    ];
+    let modal = Paragraph::new(Text::from(lines))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .title(" Pause ")
+                .borders(Borders::ALL)
+                .border_type(theme.border(BorderType::Double))
+                .border_style(Style::default().fg(Color::Cyan)),
+        );
+    f.render_widget(modal, area);
+}
+
+fn render_skip_modal(f: &mut Frame, game: &GameState) {
+    let theme = game.settings.theme;
+    let area = centered_rect(50, 30, f.area());
+    f.render_widget(Clear, area);
+
+    let monsters = game.dungeon.iter().filter(|c| c.is_monster()).count();
+    let whereto = match game.settings.skip_order {
+        SkipOrder::Bottom => "These four go to the bottom, so you'll face them last.",
+        SkipOrder::Shuffle => "These four are shuffled back into what's left.",
+        SkipOrder::Front => "These four come back right after the next room.",
+    };
+    let lines = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            "Skip this room?",
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(format!("Only {} cards left in the dungeon, {} of them monsters.", game.dungeon.len(), monsters)),
+        Line::from(Span::styled(whereto, Style::default().fg(Color::DarkGray))),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("[S/Y] ", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+            Span::styled("Skip anyway", Style::default().fg(Color::Red)),
+        ]),
+        Line::from(vec![
+            Span::styled("[any] ", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+            Span::styled("Stay in the room", Style::default().fg(Color::Green)),
+        ]),
+    ];
+    let modal = Paragraph::new(Text::from(lines))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .title(" Skip ")
+                .borders(Borders::ALL)
+                .border_type(theme.border(BorderType::Double))
+                .border_style(Style::default().fg(Color::Yellow)),
+        );
+    f.render_widget(modal, area);
+}
+
+fn render_mulligan_modal(f: &mut Frame, game: &GameState) {
+    let theme = game.settings.theme;
+    let area = centered_rect(50, 30, f.area());
+    f.render_widget(Clear, area);
+
+    let lines = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            "Take a mulligan?",
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(format!(
+            "Four monsters, no weapon, and even the cheapest three cost {} HP or more.",
+            game.health
+        )),
+        Line::from(Span::styled(
+            "You get one redeal on a new seed; the run is marked as a mulligan.",
+            Style::default().fg(Color::DarkGray),
+        )),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("[M/Y] ", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+            Span::styled("Redeal", Style::default().fg(Color::Yellow)),
+        ]),
+        Line::from(vec![
+            Span::styled("[any] ", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+            Span::styled("Keep this dungeon", Style::default().fg(Color::Green)),
+        ]),
+    ];
+    let modal = Paragraph::new(Text::from(lines))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .title(" Mulligan ")
+                .borders(Borders::ALL)
+                .border_type(theme.border(BorderType::Double))
+                .border_style(Style::default().fg(Color::Yellow)),
+        );
+    f.render_widget(modal, area);
+}
+
+fn render_quit_modal(f: &mut Frame, theme: Theme) {
     let area = centered_rect(50, 45, f.area());
     f.render_widget(Clear, area);
 
-    let door_art = r#"
+    let door_art = Glyphs::get().art(
+        r#"
             ▄▄▄▄▄▄▄▄▄▄▄▄▄
           ▄█░░░░░░░░░░░░░█▄
          ██░░░░░░░░░░░░░░░██
@@ -1253,7 +6911,18 @@ fn render_quit_modal(f: &mut Frame) {
          ██░░░░░░░░░░░░░░░██
          ██░░░░░░░░░░░░░░░██
          ██▄▄▄▄▄▄▄▄▄▄▄▄▄▄▄██
-"#;
+"#,
+        r#"
+          _______________
+         |  ___________  |
+         | |           | |
+         | |           | |
+         | |        o  | |
+         | |           | |
+         | |           | |
+         |_|___________|_|
+"#,
+    );
 
     let mut lines: Vec<Line> = door_art
         .lines()
@@ -1284,15 +6953,49 @@ fn render_quit_modal(f: &mut Frame) {
         .alignment(Alignment::Center)
         .block(
             Block::default()
-                .title(" ⚔️  Exit ⚔️  ")
+                .title(format!(" {}Exit{} ", theme.icon("⚔️  "), theme.icon(" ⚔️ ")))
                 .borders(Borders::ALL)
-                .border_type(BorderType::Double)
+                .border_type(theme.border(BorderType::Double))
                 .border_style(Style::default().fg(Color::Yellow)),
         );
 
     f.render_widget(quit_modal, area);
 }
 
+fn render_damage_popup(f: &mut Frame, popup: &DamagePopup) {
+    let text = format!("-{}", popup.amount);
+    let age = POPUP_TICKS - popup.ticks_left;
+    // Start mid-card and drift up one row every few ticks
+    let y = (popup.area.y + popup.area.height / 2).saturating_sub(age as u16 / 3);
+    let width = text.len() as u16;
+    let area = Rect {
+        x: popup.area.x + popup.area.width.saturating_sub(width) / 2,
+        y,
+        width,
+        height: 1,
+    }
+    .intersection(f.area());
+
+    let style = match popup.ticks_left {
+        7.. => Style::default().fg(damage_color(popup.amount)).add_modifier(Modifier::BOLD),
+        4..=6 => Style::default().fg(Color::LightRed),
+        _ => Style::default().fg(Color::DarkGray),
+    };
+    f.render_widget(Paragraph::new(text).style(style), area);
+}
+
+fn render_too_small(f: &mut Frame, area: Rect) {
+    let text = format!(
+        "Terminal too small ({}x{})\nNeed at least {}x{}",
+        area.width, area.height, MIN_WIDTH, MIN_HEIGHT
+    );
+    let warning = Paragraph::new(text)
+        .style(Style::default().fg(Color::Yellow))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    f.render_widget(warning, area);
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -1303,12 +7006,554 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         ])
         .split(r);
 
-    Layout::default()
+    let area = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
             Constraint::Percentage((100 - percent_x) / 2),
             Constraint::Percentage(percent_x),
             Constraint::Percentage((100 - percent_x) / 2),
         ])
-        .split(popup_layout[1])[1]
+        .split(popup_layout[1])[1];
+
+    // Percentages can round down to nothing on tiny terminals
+    Rect {
+        width: area.width.max(1),
+        height: area.height.max(1),
+        ..area
+    }
+    .intersection(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards(codes: &[&str]) -> Vec<Card> {
+        codes.iter().map(|code| Card::parse(code).unwrap()).collect()
+    }
+
+    /// A game on a fixed seed with `room` dealt in place of its first room,
+    /// kept off the disk.
+    fn game_with_room(settings: Settings, room: &[&str]) -> GameState {
+        let mut game = GameState::with_seed(settings, 1);
+        game.persist = false;
+        game.room = cards(room);
+        game
+    }
+
+    fn with_potions(potions: PotionRule) -> Settings {
+        Settings { potions, ..Settings::default() }
+    }
+
+    // If one of these fails, every seed deals a different dungeon than it
+    // used to: shared seeds, ghosts and replays all break
+
+    #[test]
+    fn seeded_rng_output_is_pinned() {
+        let mut rng = seeded_rng(42);
+        assert_eq!(rng.next_u32(), 572990626);
+        assert_eq!(below(&mut rng, 44), 23);
+        assert_eq!(below(&mut rng, 7), 1);
+    }
+
+    #[test]
+    fn seed_deals_a_known_dungeon() {
+        let game = GameState::with_seed(Settings::default(), 42);
+        let expected = cards(&["H7", "S10", "H5", "S9", "D5", "C7", "C3", "S4", "C9", "C8", "S7", "D7"]);
+        assert_eq!(game.initial_order[..12], expected[..]);
+    }
+
+    #[test]
+    fn unshuffled_deck_order() {
+        let deck = unshuffled_deck(10);
+        assert_eq!(deck.len(), 44);
+        let mut expected = Vec::new();
+        for (suit, top) in [(Suit::Spades, 14), (Suit::Clubs, 14), (Suit::Hearts, 10), (Suit::Diamonds, 10)] {
+            expected.extend((2..=top).map(|rank| Card { suit, rank }));
+        }
+        assert_eq!(deck, expected);
+    }
+
+    #[test]
+    fn unshuffled_deck_caps_red_cards() {
+        let deck = unshuffled_deck(8);
+        assert_eq!(deck.len(), 40);
+        let red = cards(&["H2", "H3", "H4", "H5", "H6", "H7", "H8", "D2", "D3", "D4", "D5", "D6", "D7", "D8"]);
+        assert_eq!(deck[26..], red[..]);
+    }
+
+    #[test]
+    fn legal_moves_in_a_fresh_room() {
+        let mut game = game_with_room(Settings::default(), &["S9", "D5", "H3", "C8"]);
+        game.weapon = Some(Weapon { card: Card { suit: Suit::Diamonds, rank: 7 }, last_monster_slain: Some(9) });
+        assert_eq!(
+            game.legal_moves(),
+            vec![
+                Action::Fight { index: 0, use_weapon: false },
+                Action::PlayWeapon(1),
+                Action::PlayPotion(2),
+                Action::Fight { index: 3, use_weapon: false },
+                Action::Fight { index: 3, use_weapon: true },
+                Action::Skip,
+            ]
+        );
+    }
+
+    #[test]
+    fn legal_moves_after_a_play_leave_out_the_skip() {
+        let mut game = game_with_room(Settings::default(), &["H3", "S9", "D5", "C8"]);
+        game.apply_action(Action::PlayPotion(0)).unwrap();
+        assert_eq!(
+            game.legal_moves(),
+            vec![
+                Action::Fight { index: 0, use_weapon: false },
+                Action::PlayWeapon(1),
+                Action::Fight { index: 2, use_weapon: false },
+            ]
+        );
+    }
+
+    #[test]
+    fn legal_moves_after_a_skip_leave_out_another() {
+        let mut game = game_with_room(Settings::default(), &["H3", "S9", "D5", "C8"]);
+        game.apply_action(Action::Skip).unwrap();
+        assert_eq!(game.room.len(), 4);
+        assert!(!game.legal_moves().contains(&Action::Skip));
+        assert!(matches!(game.apply_action(Action::Skip), Err(MoveError::CannotSkip(_))));
+    }
+
+    /// Play out a last room of a weapon, a monster it kills unhurt and a
+    /// potion, in `order` (indexes into what's left each time), from `health`.
+    fn win_final_room(settings: Settings, health: i32, order: [&str; 3]) -> GameState {
+        let mut game = game_with_room(settings, &["D3", "S2", "H5"]);
+        game.dungeon.clear();
+        game.health = health;
+        for code in order {
+            let card = Card::parse(code).unwrap();
+            let index = game.room.iter().position(|&c| c == card).unwrap();
+            let action = match code {
+                "D3" => Action::PlayWeapon(index),
+                "S2" => Action::Fight { index, use_weapon: true },
+                _ => Action::PlayPotion(index),
+            };
+            game.apply_action(action).unwrap();
+        }
+        assert!(game.won);
+        game
+    }
+
+    #[test]
+    fn win_below_max_scores_health_alone() {
+        let game = win_final_room(Settings::default(), 10, ["D3", "S2", "H5"]);
+        assert_eq!(game.health, 15);
+        assert_eq!(game.calculate_score(), 15);
+    }
+
+    #[test]
+    fn win_at_max_on_a_potion_adds_its_value() {
+        let game = win_final_room(Settings::default(), 20, ["D3", "S2", "H5"]);
+        assert_eq!(game.calculate_score(), 25);
+    }
+
+    #[test]
+    fn win_at_max_on_another_card_gets_no_bonus() {
+        let game = win_final_room(Settings::default(), 20, ["H5", "D3", "S2"]);
+        assert_eq!(game.calculate_score(), 20);
+    }
+
+    #[test]
+    fn classic_bonus_off_scores_health_alone() {
+        let settings = Settings { classic_bonus: false, ..Settings::default() };
+        let game = win_final_room(settings, 20, ["D3", "S2", "H5"]);
+        assert_eq!(game.calculate_score(), 20);
+    }
+
+    #[test]
+    fn centered_rect_stays_inside_a_tiny_area() {
+        let area = Rect::new(0, 0, 10, 5);
+        for (x, y) in [(1, 1), (50, 30), (100, 100)] {
+            let rect = centered_rect(x, y, area);
+            assert!(rect.width >= 1 && rect.height >= 1);
+            assert_eq!(rect.intersection(area), rect);
+        }
+    }
+
+    #[test]
+    fn every_screen_draws_in_a_10x5_terminal() {
+        let screens = [
+            Screen::Game,
+            Screen::Combat,
+            Screen::Help,
+            Screen::CardHelp,
+            Screen::DullingHelp,
+            Screen::Log,
+            Screen::GameOver,
+            Screen::ConfirmQuit,
+            Screen::Export,
+            Screen::Stats,
+            Screen::Graveyard,
+            Screen::Results,
+            Screen::TurnSummary,
+            Screen::Histogram,
+            Screen::Paused,
+            Screen::Resume,
+            Screen::ConfirmSkip,
+            Screen::ConfirmMulligan,
+            Screen::DebugPrompt,
+            Screen::DebugDeck,
+        ];
+        for screen in screens {
+            let mut game = game_with_room(Settings::default(), &["S9", "D5", "H3", "C8"]);
+            game.combat_card_index = Some(0);
+            game.screen = screen;
+            let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(10, 5)).unwrap();
+            terminal.draw(|f| ui(f, &mut game)).unwrap();
+        }
+    }
+
+    #[test]
+    fn results_scroll_stops_at_the_content() {
+        let mut game = game_with_room(Settings::default(), &["S9", "D5", "H3", "C8"]);
+        game.screen = Screen::Results;
+        game.results_scroll = u16::MAX;
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(80, 24)).unwrap();
+        terminal.draw(|f| ui(f, &mut game)).unwrap();
+        let clamped = game.results_scroll;
+        assert!(clamped < 24);
+        // Drawing again keeps the same bottom line
+        terminal.draw(|f| ui(f, &mut game)).unwrap();
+        assert_eq!(game.results_scroll, clamped);
+    }
+
+    /// A `--log-json` stream the test can read back, or one that refuses writes.
+    #[derive(Clone, Default)]
+    struct SharedBuf(std::rc::Rc<std::cell::RefCell<Vec<u8>>>, bool);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.1 {
+                return Err(io::Error::other("full"));
+            }
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn a_failed_log_stream_leaves_a_warning() {
+        let mut game = game_with_room(Settings::default(), &["S9", "D5", "H3", "C8"]);
+        game.log_stream = Some(Box::new(SharedBuf(Default::default(), true)));
+        game.log(LogEvent::Debug("first".to_string()));
+        assert!(game.log_stream.is_none());
+        assert!(matches!(game.log.last().map(|e| &e.event), Some(LogEvent::Warning(_))));
+    }
+
+    #[test]
+    fn resuming_replays_only_the_kept_run() {
+        let mut earlier = GameState::with_seed(Settings::default(), 3);
+        earlier.persist = false;
+        let play = earlier.legal_moves()[0];
+        earlier.apply_action(play).unwrap();
+        let checkpoint = Checkpoint {
+            seed: earlier.seed,
+            rules: String::new(),
+            deck: earlier.initial_order.clone(),
+            moves: earlier.history.clone(),
+            turn: earlier.turn_number,
+            summary: None,
+        };
+        let mut game = GameState::with_seed(Settings::default(), 4);
+        game.persist = false;
+        game.log(LogEvent::Debug("fresh deal only".to_string()));
+        let buf = SharedBuf::default();
+        game.log_stream = Some(Box::new(buf.clone()));
+        game.pending_resume = Some(checkpoint);
+        game.answer_resume(true);
+        let written = String::from_utf8(buf.0.borrow().clone()).unwrap();
+        assert!(!written.contains("fresh deal only"));
+        assert_eq!(written.lines().count(), game.log.len());
+    }
+
+    #[test]
+    fn flawless_room_message_has_no_leading_dash() {
+        let mut game = game_with_room(Settings::default(), &["S9"]);
+        game.dungeon = cards(&["H2", "H3", "D2", "C2"]);
+        game.played_this_turn = cards(&["S2", "D10", "C3"]);
+        game.cards_played_this_turn = 3;
+        game.room_damage = 0;
+        game.message.clear();
+        game.check_turn_complete();
+        assert_eq!(game.message, "Flawless room! New room!");
+    }
+
+    #[test]
+    fn score_assist_shows_the_score_of_stopping_now() {
+        let settings = Settings { assist: true, ..Settings::default() };
+        let mut game = game_with_room(settings, &["S9", "D5", "H3", "C8"]);
+        game.assist_mode = true;
+        game.dungeon = cards(&["C4"]);
+        let line = game.score_assist().unwrap();
+        // 20 HP less the 9 + 8 + 4 of monsters still to face
+        assert!(line.ends_with("(if you stopped now: -1)"), "{}", line);
+    }
+
+    #[test]
+    fn dulling_help_names_the_last_kill_by_rank() {
+        let mut game = game_with_room(Settings::default(), &["S9", "D5", "H3", "C8"]);
+        game.weapon = Some(Weapon { card: Card::parse("D8").unwrap(), last_monster_slain: Some(12) });
+        let help = game.dulling_help();
+        assert!(help[0].contains("last slew a Q"), "{}", help[0]);
+    }
+
+    #[test]
+    fn expanded_controls_fit_80_columns() {
+        for keys in [KeyMap::Standard, KeyMap::Vim] {
+            let settings = Settings { expanded_controls: true, keys, ..Settings::default() };
+            let mut game = game_with_room(settings, &["S9", "D5", "H3", "C8"]);
+            let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(80, 40)).unwrap();
+            terminal.draw(|f| ui(f, &mut game)).unwrap();
+            let buffer = terminal.backend().buffer();
+            let rows: Vec<String> = (0..40)
+                .map(|y| (0..80).map(|x| buffer[(x, y)].symbol()).collect::<String>())
+                .collect();
+            for group in ["Play  ", "Game  ", "View  ", "More  "] {
+                assert!(rows.iter().any(|row| row.contains(group)), "{} missing", group);
+            }
+            // The last key of each group made it on screen, so nothing was cut
+            for key in ["S: skip", "Q: quit", "G: monsters", "?: help"] {
+                assert!(rows.iter().any(|row| row.contains(key)), "{} cut off", key);
+            }
+        }
+    }
+
+    #[test]
+    fn debug_card_only_deals_from_the_dungeon() {
+        let mut game = game_with_room(Settings::default(), &["S9", "D5", "H3"]);
+        game.dungeon = cards(&["C4", "H5"]);
+        game.run_debug_command("card S2");
+        assert_eq!(game.room, cards(&["S9", "D5", "H3"]));
+        assert!(game.message.contains("isn't left in the dungeon"), "{}", game.message);
+        game.run_debug_command("card H5");
+        assert_eq!(game.room, cards(&["S9", "D5", "H3", "H5"]));
+        assert_eq!(game.dungeon, cards(&["C4"]));
+    }
+
+    #[test]
+    fn ascii_glyphs_draw_help_quit_and_game_over_in_ascii() {
+        ASCII.store(true, Ordering::Relaxed);
+        let settings = Settings { ascii: true, ..Settings::default() };
+        let screens = [(Screen::Help, false), (Screen::ConfirmQuit, false), (Screen::GameOver, true), (Screen::GameOver, false)];
+        for (screen, won) in screens {
+            let mut game = game_with_room(settings.clone(), &["S9", "D5", "H3", "C8"]);
+            game.screen = screen;
+            game.game_over = screen == Screen::GameOver;
+            game.won = won;
+            game.bot_score = Some(0);
+            let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(120, 60)).unwrap();
+            terminal.draw(|f| ui(f, &mut game)).unwrap();
+            let buffer = terminal.backend().buffer();
+            for cell in &buffer.content {
+                assert!(cell.symbol().is_ascii(), "won: {}, drew {:?}", won, cell.symbol());
+            }
+        }
+        ASCII.store(false, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn undo_takes_back_one_card_and_keeps_the_clock() {
+        let settings = Settings { safe: true, ..Settings::default() };
+        let mut game = game_with_room(settings, &["S9", "D5", "H3", "C8"]);
+        game.initial_order = [game.room.clone(), game.dungeon.clone()].concat();
+        game.apply_action(Action::PlayWeapon(1)).unwrap();
+        game.apply_action(Action::PlayPotion(1)).unwrap();
+        game.play_ticks = 450;
+        game.ticks = 500;
+        game.log(LogEvent::Warning("kept".to_string()));
+        game.undo();
+        assert_eq!(game.history, vec![Action::PlayWeapon(1)]);
+        assert_eq!(game.room, cards(&["S9", "H3", "C8"]));
+        assert_eq!((game.play_ticks, game.ticks), (450, 500));
+        assert!(game.log.iter().any(|e| matches!(&e.event, LogEvent::Warning(w) if w == "kept")));
+        assert!(matches!(game.log.last().map(|e| &e.event), Some(LogEvent::Undone(_))));
+    }
+
+    #[test]
+    fn rule_values_override_a_preset_from_any_source() {
+        let env = vec![("SCOUNDREL_MAX_HP".to_string(), "30".to_string())];
+        let cli = vec!["--difficulty".to_string(), "hard".to_string()];
+        let (settings, _) = Settings::layer(Vec::new(), "", env, cli).unwrap();
+        assert!(settings.difficulty == Difficulty::Hard);
+        assert_eq!(settings.max_health, 30);
+        assert_eq!(settings.red_max_rank, 8);
+
+        // The command line's preset still beats the config file's
+        let config = vec![("difficulty".to_string(), "easy".to_string())];
+        let cli = vec!["--difficulty".to_string(), "hard".to_string()];
+        let (settings, _) = Settings::layer(config, "", Vec::new(), cli).unwrap();
+        assert_eq!(settings.max_health, 15);
+    }
+
+    fn key(code: KeyCode, kind: KeyEventKind) -> KeyEvent {
+        KeyEvent { kind, ..KeyEvent::from(code) }
+    }
+
+    #[test]
+    fn held_arrows_repeat_but_held_enter_does_not() {
+        let mut game = game_with_room(Settings::default(), &["S9", "D5", "H3", "C8"]);
+        let mut last_action = None;
+        handle_key(&mut game, key(KeyCode::Right, KeyEventKind::Press), &mut last_action);
+        handle_key(&mut game, key(KeyCode::Right, KeyEventKind::Repeat), &mut last_action);
+        assert_eq!(game.selected_index, 2);
+        handle_key(&mut game, key(KeyCode::Enter, KeyEventKind::Repeat), &mut last_action);
+        assert_eq!(game.room.len(), 4);
+        assert_eq!(game.moves, 0);
+    }
+
+    #[test]
+    fn injected_rng_drives_the_shuffle() {
+        // Always drawing index 0, Fisher–Yates rotates the deck by one
+        let mut zeros = rand::rngs::mock::StepRng::new(0, 0);
+        let game = GameState::with_rng(Settings::default(), 0, &mut zeros);
+        let mut expected = unshuffled_deck(10);
+        expected.rotate_left(1);
+        assert_eq!(game.initial_order, expected);
+    }
+
+    #[test]
+    fn same_seed_same_deck() {
+        let first = GameState::with_seed(Settings::default(), 7);
+        let second = GameState::with_seed(Settings::default(), 7);
+        let other = GameState::with_seed(Settings::default(), 8);
+        assert_eq!(first.initial_order, second.initial_order);
+        assert_ne!(first.initial_order, other.initial_order);
+    }
+
+    #[test]
+    fn number_key_past_a_shrunk_room_is_refused() {
+        let mut game = game_with_room(Settings::default(), &["H3", "S2", "D5", "C4"]);
+        game.play_selected(0).unwrap();
+        assert_eq!(game.room.len(), 3);
+        assert!(matches!(game.play_selected(3), Err(MoveError::IndexOutOfRange { index: 3, len: 3 })));
+        assert_eq!(game.room.len(), 3);
+    }
+
+    #[test]
+    fn number_key_plays_the_card_now_in_that_slot() {
+        let mut game = game_with_room(Settings::default(), &["H3", "S2", "D5", "C4"]);
+        game.play_selected(0).unwrap();
+        // 3 is now the club; the diamond moved up to 2
+        game.play_selected(1).unwrap();
+        assert_eq!(game.weapon.as_ref().map(|w| w.card), Card::parse("D5"));
+        assert_eq!(game.room, cards(&["S2", "C4"]));
+    }
+
+    #[test]
+    fn mismatched_actions_are_refused() {
+        let mut game = game_with_room(Settings::default(), &["H3", "S2", "D5", "C4"]);
+        let wrong = [
+            Action::PlayWeapon(0),
+            Action::Fight { index: 0, use_weapon: false },
+            Action::PlayPotion(1),
+            Action::PlayWeapon(1),
+            Action::PlayPotion(2),
+            Action::Fight { index: 2, use_weapon: false },
+            Action::FightWithSpare(2),
+        ];
+        for action in wrong {
+            assert!(
+                matches!(game.apply_action(action), Err(MoveError::WrongCardType { .. })),
+                "{:?} went through",
+                action
+            );
+        }
+        assert_eq!(game.room.len(), 4);
+        assert_eq!(game.health, 20);
+        assert!(game.history.is_empty());
+    }
+
+    #[test]
+    fn solver_line_stops_short_instead_of_panicking() {
+        // As when the memo is full and nothing after the start settles
+        let settings = Settings::default();
+        let game = GameState::with_seed(settings.clone(), 1);
+        let mut solver = Solver::new(&settings);
+        let (line, complete) = solver.line(&Position::of(&game), |_, _| false);
+        assert!(line.is_empty());
+        assert!(!complete);
+    }
+
+    #[test]
+    fn debug_safe_and_mulligan_runs_are_unranked() {
+        let debug = GameState::with_seed(Settings { debug: true, ..Settings::default() }, 1);
+        let safe = GameState::with_seed(Settings { safe: true, ..Settings::default() }, 1);
+        let mut mulligan = GameState::with_seed(Settings::default(), 1);
+        mulligan.mulligan_used = true;
+        for game in [debug, safe, mulligan] {
+            assert!(game.unranked_reason().is_some());
+        }
+        assert!(GameState::with_seed(Settings::default(), 1).unranked_reason().is_none());
+    }
+
+    #[test]
+    fn vim_shift_l_opens_the_log_from_game_over() {
+        assert_eq!(KeyMap::Vim.translate(Screen::GameOver, KeyCode::Char('L')), KeyCode::Char('l'));
+    }
+
+    #[test]
+    fn strict_potions_waste_the_second() {
+        let mut game = game_with_room(with_potions(PotionRule::OnePerTurn), &["H5", "H7", "S2", "S3"]);
+        game.health = 10;
+        game.apply_action(Action::PlayPotion(0)).unwrap();
+        game.apply_action(Action::PlayPotion(0)).unwrap();
+        assert_eq!(game.health, 15);
+        assert_eq!(game.potions_drunk, 1);
+    }
+
+    #[test]
+    fn forgiving_potions_all_heal() {
+        let mut game = game_with_room(with_potions(PotionRule::Forgiving), &["H5", "H7", "S2", "S3"]);
+        game.health = 5;
+        game.apply_action(Action::PlayPotion(0)).unwrap();
+        game.apply_action(Action::PlayPotion(0)).unwrap();
+        assert_eq!(game.health, 17);
+        assert_eq!(game.potions_drunk, 2);
+    }
+
+    #[test]
+    fn best_potion_heals_the_larger_at_turn_end() {
+        let mut game = game_with_room(with_potions(PotionRule::Best), &["H5", "H7", "S2", "S3"]);
+        game.health = 10;
+        game.apply_action(Action::PlayPotion(0)).unwrap();
+        game.apply_action(Action::PlayPotion(0)).unwrap();
+        assert_eq!(game.health, 10);
+        game.apply_action(Action::Fight { index: 0, use_weapon: false }).unwrap();
+        assert_eq!(game.health, 15);
+        assert_eq!(game.potions_drunk, 1);
+    }
+
+    #[test]
+    fn best_potion_wastes_a_smaller_second() {
+        let mut game = game_with_room(with_potions(PotionRule::Best), &["H7", "H5", "S2", "S3"]);
+        game.health = 10;
+        game.apply_action(Action::PlayPotion(0)).unwrap();
+        game.apply_action(Action::PlayPotion(0)).unwrap();
+        game.apply_action(Action::Fight { index: 0, use_weapon: false }).unwrap();
+        assert_eq!(game.health, 15);
+        assert_eq!(game.avoidable_mistakes, 1);
+    }
+
+    #[test]
+    fn best_potion_does_not_heal_before_a_fight_in_the_same_turn() {
+        // Healed on drinking, the 7 would carry 8 HP through the 9
+        let mut game = game_with_room(with_potions(PotionRule::Best), &["H7", "S9", "S2", "S3"]);
+        game.health = 8;
+        game.apply_action(Action::PlayPotion(0)).unwrap();
+        game.apply_action(Action::Fight { index: 0, use_weapon: false }).unwrap();
+        assert!(game.game_over && !game.won);
+    }
+
 }
@@ -0,0 +1,254 @@
+//! Everything that touches the disk: where files live, and turning I/O
+//! failures into short warnings the UI can show without giving up.
+
+use std::{fmt, fs, io, io::Read, io::Write, path::Path, path::PathBuf};
+
+/// A read or write that didn't happen, e.g. "Couldn't save replay:
+/// permission denied".
+#[derive(Debug)]
+pub struct StorageError {
+    action: &'static str,
+    reason: String,
+}
+
+impl StorageError {
+    fn new(action: &'static str, err: &io::Error) -> Self {
+        let reason = match err.kind() {
+            io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+            io::ErrorKind::ReadOnlyFilesystem => "read-only file system".to_string(),
+            io::ErrorKind::StorageFull => "disk full".to_string(),
+            io::ErrorKind::NotFound => "not found".to_string(),
+            _ => err.to_string(),
+        };
+        StorageError { action, reason }
+    }
+
+    /// No `$HOME` (or XDG override) to put the file under.
+    pub fn no_home(action: &'static str) -> Self {
+        StorageError {
+            action,
+            reason: "no home directory".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Couldn't {}: {}", self.action, self.reason)
+    }
+}
+
+/// `$XDG_<VAR>/scoundrel`, else `~/<fallback>/scoundrel`.
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os(var).filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("scoundrel"));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback).join("scoundrel"))
+}
+
+/// Where run data lives: `$XDG_DATA_HOME/scoundrel`, else `~/.local/share/scoundrel`.
+pub fn data_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// Where the optional config file lives: `$XDG_CONFIG_HOME/scoundrel`, else
+/// `~/.config/scoundrel`.
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config"))
+}
+
+/// One file per seed, holding the last finished run on it.
+pub fn replays_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("replays"))
+}
+
+pub fn replay_path(seed: u64) -> Option<PathBuf> {
+    replays_dir().map(|dir| dir.join(format!("{}.txt", seed)))
+}
+
+/// The `--autosave` checkpoint of the run in progress.
+pub fn checkpoint_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("checkpoint.txt"))
+}
+
+/// One row per finished run, for spreadsheets.
+pub fn runs_csv_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("runs.csv"))
+}
+
+/// The file's contents, or `None` if there is no such file. Any other
+/// failure is an error.
+pub fn read(path: &Path, action: &'static str) -> Result<Option<String>, StorageError> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(StorageError::new(action, &err)),
+    }
+}
+
+/// The contents of every `.txt` file in the directory; a directory that
+/// doesn't exist yet holds none.
+pub fn read_all(dir: &Path, action: &'static str) -> Result<Vec<String>, StorageError> {
+    read_all_at(dir).map_err(|err| StorageError::new(action, &err))
+}
+
+fn read_all_at(dir: &Path) -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut texts = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "txt") {
+            texts.push(fs::read_to_string(path)?);
+        }
+    }
+    Ok(texts)
+}
+
+/// Write the file, creating its directory first.
+pub fn write(path: &Path, contents: &str, action: &'static str) -> Result<(), StorageError> {
+    write_at(path, contents).map_err(|err| StorageError::new(action, &err))
+}
+
+fn write_at(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents)
+}
+
+/// Like `write`, but through a temporary file renamed into place, so a
+/// crash mid-write leaves the old contents rather than half of the new.
+pub fn replace(path: &Path, contents: &str, action: &'static str) -> Result<(), StorageError> {
+    let temp = path.with_extension("tmp");
+    write_at(&temp, contents)
+        .and_then(|()| fs::rename(&temp, path))
+        .map_err(|err| StorageError::new(action, &err))
+}
+
+/// Add a line to the end of a CSV file, writing `header` first if the file
+/// is new or empty. A file written under an older header is brought up to
+/// date first: if `header` only adds columns at the end, its rows get empty
+/// fields for them; otherwise it's moved aside to `<name>.old.csv` and a
+/// new file started.
+pub fn append_csv_row(path: &Path, header: &str, row: &str, action: &'static str) -> Result<(), StorageError> {
+    append_at(path, header, row).map_err(|err| StorageError::new(action, &err))
+}
+
+fn append_at(path: &Path, header: &str, row: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    migrate_csv(path, header)?;
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    let mut text = String::new();
+    if file.metadata()?.len() == 0 {
+        text.push_str(header);
+        text.push('\n');
+    }
+    text.push_str(row);
+    text.push('\n');
+    file.write_all(text.as_bytes())
+}
+
+/// Like `read`, but holding a shared lock on the file while it's read, so
+/// another instance's `append_locked` is never seen half done.
+pub fn read_locked(path: &Path, action: &'static str) -> Result<Option<String>, StorageError> {
+    let mut file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(StorageError::new(action, &err)),
+    };
+    let mut text = String::new();
+    file.lock_shared()
+        .and_then(|()| file.read_to_string(&mut text))
+        .map_err(|err| StorageError::new(action, &err))?;
+    Ok(Some(text))
+}
+
+/// Add a line to the end of a file that other instances may be writing to
+/// at the same time, holding an exclusive lock on it until the line is in.
+/// The lock is released when the file is closed.
+pub fn append_locked(path: &Path, line: &str, action: &'static str) -> Result<(), StorageError> {
+    append_locked_at(path, line).map_err(|err| StorageError::new(action, &err))
+}
+
+fn append_locked_at(path: &Path, line: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.lock()?;
+    file.write_all(format!("{}\n", line).as_bytes())
+}
+
+fn migrate_csv(path: &Path, header: &str) -> io::Result<()> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let Some(old_header) = text.lines().next().filter(|line| *line != header) else {
+        return Ok(());
+    };
+    match header.strip_prefix(old_header).filter(|added| added.starts_with(',')) {
+        Some(added) => {
+            let padding = ",".repeat(added.matches(',').count());
+            let mut migrated = format!("{}\n", header);
+            for line in text.lines().skip(1) {
+                migrated.push_str(line);
+                migrated.push_str(&padding);
+                migrated.push('\n');
+            }
+            let temp = path.with_extension("tmp");
+            fs::write(&temp, migrated)?;
+            fs::rename(&temp, path)
+        }
+        None => fs::rename(path, path.with_extension("old.csv")),
+    }
+}
+
+/// Delete the file; one that's already gone is fine.
+pub fn remove(path: &Path, action: &'static str) -> Result<(), StorageError> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(StorageError::new(action, &err)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("scoundrel-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn csv_gains_new_columns_on_old_rows() {
+        let path = scratch_file("added.csv");
+        fs::write(&path, "a,b\n1,2\n").unwrap();
+        append_csv_row(&path, "a,b,c", "3,4,5", "test").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a,b,c\n1,2,\n3,4,5\n");
+    }
+
+    #[test]
+    fn csv_under_another_header_is_moved_aside() {
+        let path = scratch_file("changed.csv");
+        fs::write(&path, "x,y\n1,2\n").unwrap();
+        append_csv_row(&path, "a,b", "3,4", "test").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a,b\n3,4\n");
+        assert_eq!(fs::read_to_string(path.with_extension("old.csv")).unwrap(), "x,y\n1,2\n");
+    }
+}